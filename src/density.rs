@@ -0,0 +1,197 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{FractalParams, Vec2, known_period};
+
+
+// Buddhabrots color each pixel by how many escaping orbits pass through it, rather than by what happens to the pixel's
+// own orbit. The counts build up over as many frames as the view sits still for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DensityMode {
+    Buddhabrot,
+    // Three buddhabrots with different iteration limits in red, green and blue
+    Nebulabrot,
+}
+
+impl DensityMode {
+    // Iteration limits for red, green and blue. Nebulabrots give green and blue a tenth and a hundredth of the pane's
+    // limit, so the short orbits that make up the wispy outer parts get their own colors.
+    pub fn limits(self, max_iterations: u32) -> [u32; 3] {
+        match self {
+            Self::Buddhabrot => [max_iterations; 3],
+            Self::Nebulabrot => [max_iterations, (max_iterations / 10).max(1), (max_iterations / 100).max(1)],
+        }
+    }
+}
+
+// How many points get iterated per frame. The cpu gets fewer, since it's only there for whatever can't run compute
+// shaders, which is mostly single threaded wasm.
+pub const DENSITY_SAMPLES_PER_FRAME: u32 = 1 << 16;
+pub const CPU_DENSITY_SAMPLES_PER_FRAME: u32 = 1 << 12;
+// Points per workgroup of cs_accumulate(), its workgroup size times SAMPLES_PER_THREAD in density.wgsl
+pub const DENSITY_WORKGROUP_SAMPLES: u32 = 64 * 16;
+
+// The pcg hash, which is plenty random for picking sample points and comes out the same on the gpu. Must match pcg()
+// in density.wgsl.
+pub fn pcg(v: u32) -> u32 {
+    let state = v.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+// The index-th point of a frame's samples, anywhere in the square around the whole set. Must match sample_point() in
+// density.wgsl.
+pub fn sample_point(seed: u32, index: u32) -> Vec2<f64> {
+    let re = pcg(seed ^ pcg(index));
+    let im = pcg(re);
+    Vec2(re as f64 / 2f64.powi(32) * 4.0 - 2.0, im as f64 / 2f64.powi(32) * 4.0 - 2.0)
+}
+
+// Square root of the count relative to the busiest pixel, since the busiest ones are far busier than the rest. Must
+// match density_brightness() in density.wgsl.
+pub fn density_brightness(count: u32, max: u32) -> f32 {
+    if max == 0 { return 0.0 }
+    (count as f32 / max as f32).sqrt()
+}
+
+
+// The cpu version, for where there are no compute shaders
+pub struct DensityImage {
+    pub width: u32,
+    pub height: u32,
+    // Red, green and blue counts for each pixel, row by row from the top
+    pub counts: Vec<AtomicU32>,
+    pub max: [AtomicU32; 3],
+}
+
+impl DensityImage {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            counts: (0..3 * width as usize * height as usize).map(|_| AtomicU32::new(0)).collect(),
+            max: Default::default(),
+        }
+    }
+    
+    pub fn clear(&mut self) {
+        for count in self.counts.iter_mut().chain(&mut self.max) { *count.get_mut() = 0 }
+    }
+    
+    // Runs the frame's samples for seed, split between however many threads the machine has. Every thread adds to the
+    // same counts, same as the gpu's invocations do. Wasm has no threads so this just runs inline there.
+    pub fn accumulate(&mut self, params: &FractalParams, limits: [u32; 3], seed: u32, samples: u32) {
+        #[cfg(not(target_arch = "wasm32"))]
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get() as u32);
+        #[cfg(target_arch = "wasm32")]
+        let threads = 1;
+        
+        let image = &*self;
+        let run = move |thread: u32| {
+            let mut max = [0; 3];
+            for index in (thread..samples).step_by(threads as usize) {
+                let busiest = image.splat(params, limits, sample_point(seed, index));
+                max = [0, 1, 2].map(|k| max[k].max(busiest[k]));
+            }
+            for (image_max, max) in image.max.iter().zip(max) { image_max.fetch_max(max, Ordering::Relaxed); }
+        };
+        
+        if threads <= 1 {
+            run(0);
+            return
+        }
+        std::thread::scope(|scope| {
+            for thread in 0..threads { scope.spawn(move || run(thread)); }
+        });
+    }
+    
+    // Adds the orbit of c to every channel it escaped under the limit for, and returns the biggest count it left behind
+    // in each. Must match splat() in density.wgsl.
+    fn splat(&self, params: &FractalParams, limits: [u32; 3], c: Vec2<f64>) -> [u32; 3] {
+        let mut busiest = [0; 3];
+        // The main cardioid and period 2 bulb never escape, and they're a big chunk of the square
+        if known_period(c) != 0 { return busiest }
+        
+        let limit = limits.into_iter().max().unwrap_or(0);
+        let step = |z: Vec2<f64>| Vec2(z.0 * z.0 - z.1 * z.1 + c.0, 2.0 * z.0 * z.1 + c.1);
+        let mut z = Vec2(0.0, 0.0);
+        let mut n = 0;
+        while n < limit && z.0 * z.0 + z.1 * z.1 <= 4.0 {
+            z = step(z);
+            n += 1;
+        }
+        if n == limit { return busiest }
+        
+        // Then again now that it's known which channels it goes in
+        let center = params.center_f64();
+        let scale = params.scale.to_f64();
+        let aspect = self.width as f64 / self.height as f64;
+        let mut z = Vec2(0.0, 0.0);
+        for _ in 0..n {
+            z = step(z);
+            let x = ((z.0 - center.0) / (scale * aspect) + 1.0) * 0.5 * self.width as f64;
+            let y = (1.0 - (z.1 - center.1) / scale) * 0.5 * self.height as f64;
+            if x < 0.0 || y < 0.0 || x >= self.width as f64 || y >= self.height as f64 { continue }
+            let pixel = 3 * (y as usize * self.width as usize + x as usize);
+            for k in 0..3 {
+                if n < limits[k] { busiest[k] = busiest[k].max(self.counts[pixel + k].fetch_add(1, Ordering::Relaxed) + 1) }
+            }
+        }
+        busiest
+    }
+    
+    pub fn rgba(&self) -> Vec<u8> {
+        let max = self.max.each_ref().map(|max| max.load(Ordering::Relaxed));
+        self.counts.chunks(3).flat_map(|counts| {
+            let [r, g, b] = [0, 1, 2].map(|k| (density_brightness(counts[k].load(Ordering::Relaxed), max[k]) * 255.0).round() as u8);
+            [r, g, b, 255]
+        }).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate_wgsl;
+    
+    fn counts(image: &DensityImage) -> Vec<u32> {
+        image.counts.iter().map(|count| count.load(Ordering::Relaxed)).collect()
+    }
+    
+    #[test]
+    fn accumulation_is_deterministic_and_clears() {
+        let params = FractalParams::default();
+        let limits = DensityMode::Buddhabrot.limits(100);
+        let mut first = DensityImage::new(32, 24);
+        let mut second = DensityImage::new(32, 24);
+        first.accumulate(&params, limits, 7, 2000);
+        second.accumulate(&params, limits, 7, 2000);
+        assert_eq!(counts(&first), counts(&second));
+        assert!(counts(&first).iter().any(|&count| count > 0));
+        // Buddhabrots put the same counts in every channel
+        let max = first.max.each_ref().map(|max| max.load(Ordering::Relaxed));
+        assert!(max[0] > 0 && max[0] == max[1] && max[1] == max[2]);
+        
+        first.clear();
+        assert!(counts(&first).iter().all(|&count| count == 0));
+        assert!(first.max.iter().all(|max| max.load(Ordering::Relaxed) == 0));
+        assert!(first.rgba().chunks(4).all(|pixel| pixel == [0, 0, 0, 255]));
+    }
+    
+    #[test]
+    fn nebulabrot_channels_nest() {
+        let params = FractalParams::default();
+        let mut image = DensityImage::new(32, 24);
+        image.accumulate(&params, DensityMode::Nebulabrot.limits(1000), 3, 4000);
+        // Anything under green's or blue's limit is under red's too
+        assert!(image.counts.chunks(3).all(|pixel| {
+            let [r, g, b] = [0, 1, 2].map(|k| pixel[k].load(Ordering::Relaxed));
+            r >= g && g >= b
+        }));
+    }
+    
+    #[test]
+    fn density_shader_is_valid() {
+        validate_wgsl(include_str!("density.wgsl")).unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+// Buddhabrot accumulation, kept out of shader.wgsl since it's compute rather than fragment work and only gets built
+// where there are compute shaders. Each invocation runs a few random points and splats the orbits of the ones that
+// escape into the counts, and a second pass turns the counts into colors for the pane to draw.
+
+struct DensityUniforms {
+    center: vec2<f32>,
+    scale: f32,
+    aspect: f32,
+    width: u32,
+    height: u32,
+    seed: u32,
+    // Red, green and blue iteration limits, and the biggest of them
+    limits: vec4<u32>,
+};
+
+struct Counts {
+    // The busiest pixel's count in each channel
+    max: array<atomic<u32>, 4>,
+    // Red, green and blue for each pixel, row by row from the top
+    counts: array<atomic<u32>>,
+};
+
+@group(0) @binding(0)
+var<uniform> d: DensityUniforms;
+@group(0) @binding(1)
+var<storage, read_write> counts: Counts;
+@group(0) @binding(2)
+var image: texture_storage_2d<rgba8unorm, write>;
+
+const SAMPLES_PER_THREAD: u32 = 16u;
+
+// Must match pcg() in density.rs
+fn pcg(v: u32) -> u32 {
+    let state = v * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Must match sample_point() in density.rs
+fn sample_point(index: u32) -> vec2<f32> {
+    let re = pcg(d.seed ^ pcg(index));
+    let im = pcg(re);
+    return vec2<f32>(f32(re), f32(im)) / 4294967296.0 * 4.0 - 2.0;
+}
+
+// The main cardioid and period 2 bulb never escape, and they're a big chunk of the square
+fn in_main_bulbs(c: vec2<f32>) -> bool {
+    let x = c.x - 0.25;
+    let q = x * x + c.y * c.y;
+    return q * (q + x) <= 0.25 * c.y * c.y || (c.x + 1.0) * (c.x + 1.0) + c.y * c.y <= 0.0625;
+}
+
+fn step(z: vec2<f32>, c: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(z.x * z.x - z.y * z.y, 2.0 * z.x * z.y) + c;
+}
+
+// Adds the orbit of c to every channel it escaped under the limit for, and returns the biggest count it left behind in
+// each. Must match DensityImage::splat() in density.rs.
+fn splat(c: vec2<f32>) -> vec3<u32> {
+    var busiest = vec3<u32>(0u);
+    if in_main_bulbs(c) { return busiest; }
+
+    var z = vec2<f32>(0.0);
+    var n = 0u;
+    for (; n < d.limits.w && dot(z, z) <= 4.0; n++) { z = step(z, c); }
+    if n == d.limits.w { return busiest; }
+
+    // Then again now that it's known which channels it goes in
+    let size = vec2<f32>(f32(d.width), f32(d.height));
+    z = vec2<f32>(0.0);
+    for (var i = 0u; i < n; i++) {
+        z = step(z, c);
+        let position = vec2<f32>((z.x - d.center.x) / (d.scale * d.aspect) + 1.0, 1.0 - (z.y - d.center.y) / d.scale) * 0.5 * size;
+        if any(position < vec2<f32>(0.0)) || any(position >= size) { continue; }
+        let pixel = 3u * (u32(position.y) * d.width + u32(position.x));
+        for (var k = 0u; k < 3u; k++) {
+            if n < d.limits[k] { busiest[k] = max(busiest[k], atomicAdd(&counts.counts[pixel + k], 1u) + 1u); }
+        }
+    }
+    return busiest;
+}
+
+@compute @workgroup_size(64)
+fn cs_accumulate(@builtin(global_invocation_id) id: vec3<u32>) {
+    var busiest = vec3<u32>(0u);
+    for (var i = 0u; i < SAMPLES_PER_THREAD; i++) {
+        busiest = max(busiest, splat(sample_point(id.x * SAMPLES_PER_THREAD + i)));
+    }
+    for (var k = 0u; k < 3u; k++) { atomicMax(&counts.max[k], busiest[k]); }
+}
+
+// Must match density_brightness() in density.rs
+fn density_brightness(count: u32, busiest: u32) -> f32 {
+    if busiest == 0u { return 0.0; }
+    return sqrt(f32(count) / f32(busiest));
+}
+
+@compute @workgroup_size(8, 8)
+fn cs_resolve(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= d.width || id.y >= d.height { return; }
+    let pixel = 3u * (id.y * d.width + id.x);
+    var color = vec3<f32>(0.0);
+    for (var k = 0u; k < 3u; k++) { color[k] = density_brightness(atomicLoad(&counts.counts[pixel + k]), atomicLoad(&counts.max[k])); }
+    textureStore(image, vec2<i32>(id.xy), vec4<f32>(color, 1.0));
+}
@@ -0,0 +1,57 @@
+
+// Double-float arithmetic. A df64 is a vec2<f32> holding the unevaluated sum x + y, and a complex df64 is a vec4<f32>
+// of (re.x, re.y, im.x, im.y). Has to stay in step with Df64 in math.rs.
+
+fn df_two_sum(a: f32, b: f32) -> vec2<f32> {
+    let s = a + b;
+    let v = s - a;
+    return vec2<f32>(s, (a - (s - v)) + (b - v));
+}
+
+// Only exact when |a| >= |b|
+fn df_quick_two_sum(a: f32, b: f32) -> vec2<f32> {
+    let s = a + b;
+    return vec2<f32>(s, b - (s - a));
+}
+
+fn df_split(a: f32) -> vec2<f32> {
+    let t = 4097.0 * a;
+    let hi = t - (t - a);
+    return vec2<f32>(hi, a - hi);
+}
+
+fn df_two_prod(a: f32, b: f32) -> vec2<f32> {
+    let p = a * b;
+    let a_split = df_split(a);
+    let b_split = df_split(b);
+    return vec2<f32>(p, ((a_split.x * b_split.x - p) + a_split.x * b_split.y + a_split.y * b_split.x) + a_split.y * b_split.y);
+}
+
+fn df_add(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    let s = df_two_sum(a.x, b.x);
+    let t = df_two_sum(a.y, b.y);
+    let u = df_quick_two_sum(s.x, s.y + t.x);
+    return df_quick_two_sum(u.x, u.y + t.y);
+}
+
+fn df_sub(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    return df_add(a, -b);
+}
+
+fn df_mul(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    let p = df_two_prod(a.x, b.x);
+    return df_quick_two_sum(p.x, p.y + (a.x * b.y + a.y * b.x));
+}
+
+fn dc_add(a: vec4<f32>, b: vec4<f32>) -> vec4<f32> {
+    return vec4<f32>(df_add(a.xy, b.xy), df_add(a.zw, b.zw));
+}
+
+fn dc_square(z: vec4<f32>) -> vec4<f32> {
+    let re_im = df_mul(z.xy, z.zw);
+    return vec4<f32>(df_sub(df_mul(z.xy, z.xy), df_mul(z.zw, z.zw)), df_add(re_im, re_im));
+}
+
+fn dc_length_squared(z: vec4<f32>) -> f32 {
+    return z.x * z.x + z.z * z.z;
+}
@@ -0,0 +1,61 @@
+// Complex numbers as a pair of f32 mantissas sharing one exponent, for perturbation deltas too small for an f32 of
+// their own. Bigger of the two mantissas is kept in [0.5, 1), and 0 gets an exponent so low it never wins an alignment.
+// Must stay in step with FloatExp in math.rs.
+
+struct ComplexExp {
+    m: vec2<f32>,
+    e: i32,
+};
+
+const CX_ZERO_EXPONENT: i32 = -0x3fffffff;
+
+fn cx_normalize(m: vec2<f32>, e: i32) -> ComplexExp {
+    let biggest = max(abs(m.x), abs(m.y));
+    if biggest == 0.0 { return ComplexExp(vec2<f32>(0.0), CX_ZERO_EXPONENT); }
+    let shift = frexp(biggest).exp;
+    return ComplexExp(ldexp(m, vec2<i32>(-shift)), e + shift);
+}
+
+fn cx_from_f32(z: vec2<f32>) -> ComplexExp {
+    return cx_normalize(z, 0);
+}
+
+// Flushes to 0 below f32's normal range, which is fine for anything that's about to be added to something bigger
+fn cx_to_f32(z: ComplexExp) -> vec2<f32> {
+    if z.e < -126 { return vec2<f32>(0.0); }
+    return ldexp(z.m, vec2<i32>(min(z.e, 127)));
+}
+
+// The mantissa as if the exponent were e, which has to be at least z's
+fn cx_aligned(z: ComplexExp, e: i32) -> vec2<f32> {
+    let shift = z.e - e;
+    if shift < -60 { return vec2<f32>(0.0); }
+    return ldexp(z.m, vec2<i32>(shift));
+}
+
+fn cx_add(a: ComplexExp, b: ComplexExp) -> ComplexExp {
+    let e = max(a.e, b.e);
+    return cx_normalize(cx_aligned(a, e) + cx_aligned(b, e), e);
+}
+
+fn cx_sub(a: ComplexExp, b: ComplexExp) -> ComplexExp {
+    return cx_add(a, ComplexExp(-b.m, b.e));
+}
+
+fn cx_mul(a: ComplexExp, b: ComplexExp) -> ComplexExp {
+    let m = vec2<f32>(a.m.x * b.m.x - a.m.y * b.m.y, a.m.x * b.m.y + a.m.y * b.m.x);
+    return cx_normalize(m, a.e + b.e);
+}
+
+// Times a plain complex number, which had better not overflow the mantissa
+fn cx_mul_f32(a: ComplexExp, b: vec2<f32>) -> ComplexExp {
+    let m = vec2<f32>(a.m.x * b.x - a.m.y * b.y, a.m.x * b.y + a.m.y * b.x);
+    return cx_normalize(m, a.e);
+}
+
+// |z| < mantissa * 2^exponent
+fn cx_smaller_than(z: ComplexExp, mantissa: f32, exponent: i32) -> bool {
+    if z.m.x == 0.0 && z.m.y == 0.0 { return mantissa > 0.0; }
+    let m = ldexp(z.m, vec2<i32>(clamp(z.e - exponent, -64, 64)));
+    return dot(m, m) < mantissa * mantissa;
+}
@@ -0,0 +1,497 @@
+use crate::{Complex, Vec2, complex_pow};
+
+
+// An iteration formula typed in at runtime, like "z = z^3 + c*sin(z)". The same expression tree runs on the cpu and
+// gets compiled to a wgsl function for the shader.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formula {
+    // The right hand side, as typed
+    pub text: String,
+    expr: Expr,
+}
+
+// Everything's complex as far as the formula is concerned, but the parts that are known to stay real get to skip the
+// complex arithmetic in the shader
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Type {
+    Real,
+    Complex,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Variable {
+    Z,
+    C,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Exp,
+    Log,
+    Sqrt,
+    Abs,
+    Arg,
+    Re,
+    Im,
+    Conj,
+}
+
+const FUNCTIONS: [(&str, Function); 14] = [
+    ("sin", Function::Sin),
+    ("cos", Function::Cos),
+    ("tan", Function::Tan),
+    ("sinh", Function::Sinh),
+    ("cosh", Function::Cosh),
+    ("tanh", Function::Tanh),
+    ("exp", Function::Exp),
+    ("log", Function::Log),
+    ("sqrt", Function::Sqrt),
+    ("abs", Function::Abs),
+    ("arg", Function::Arg),
+    ("re", Function::Re),
+    ("im", Function::Im),
+    ("conj", Function::Conj),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    // Numbers and the named constants i, pi and e
+    Constant(Complex),
+    Variable(Variable),
+    Negate(Box<Expr>),
+    Binary(Operator, Box<Expr>, Box<Expr>),
+    Call(Function, Box<Expr>),
+}
+
+// How deep the expression tree can get. Everything that walks it recurses, so past this it could run out of stack.
+const MAX_FORMULA_DEPTH: usize = 256;
+
+// The line in shader.wgsl that gets swapped out for the formula's step
+const CUSTOM_STEP_SIGNATURE: &str = "fn custom_step(z: vec2<f32>, c: vec2<f32>) -> vec2<f32>";
+
+impl Default for Formula {
+    fn default() -> Self {
+        "z^2 + c".parse().unwrap()
+    }
+}
+
+impl std::str::FromStr for Formula {
+    type Err = crate::Error;
+    fn from_str(s: &str) -> crate::Result<Self> {
+        let tokens = tokenize(s)?;
+        // "z =" in front is optional, since z is the only thing there is to assign to
+        let tokens = match &tokens[..] {
+            [Token::Name(name), Token::Symbol('='), rest @ ..] if name == "z" => rest,
+            tokens => tokens,
+        };
+        let mut parser = Parser { tokens, position: 0, depth: 0 };
+        let expr = parser.sum()?;
+        if let Some(token) = parser.peek() { return Err(format!("unexpected {token} in the formula").into()) }
+        let text = match s.split_once('=') {
+            Some((_, rest)) => rest.trim(),
+            None => s.trim(),
+        };
+        Ok(Self { text: text.to_string(), expr })
+    }
+}
+
+impl Formula {
+    pub fn evaluate(&self, z: Complex, c: Complex) -> Complex {
+        self.expr.evaluate(z, c)
+    }
+    
+    // The formula as the shader's custom_step()
+    pub fn wgsl(&self) -> String {
+        format!("{CUSTOM_STEP_SIGNATURE} {{ return {}; }}", self.expr.complex_wgsl())
+    }
+    
+    // Swaps the stand-in custom_step() in a shader for this formula's
+    pub fn splice(&self, shader: &str) -> String {
+        let start = shader.find(CUSTOM_STEP_SIGNATURE).expect("the shader has a custom_step() to splice over");
+        let end = shader[start..].find(['\r', '\n']).map_or(shader.len(), |end| start + end);
+        format!("{}{}{}", &shader[..start], self.wgsl(), &shader[end..])
+    }
+}
+
+// Runs a shader through naga the way wgpu would, for tests that have no device to hand it to
+#[cfg(test)]
+pub fn validate_wgsl(source: &str) -> crate::Result<()> {
+    let module = naga::front::wgsl::parse_str(source).map_err(|e| format!("wgsl: {}", e.message()))?;
+    let mut validator = naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::default());
+    validator.validate(&module).map_err(|e| {
+        // The outer error only says which function, the reason is further down the chain
+        let mut message = e.as_inner().to_string();
+        let mut source = std::error::Error::source(e.as_inner());
+        while let Some(error) = source {
+            message += &format!(": {error}");
+            source = error.source();
+        }
+        format!("wgsl: {message}")
+    })?;
+    Ok(())
+}
+
+
+impl Expr {
+    fn ty(&self) -> Type {
+        match self {
+            Self::Constant(x) => if x.im == 0.0 { Type::Real } else { Type::Complex },
+            Self::Variable(_) => Type::Complex,
+            Self::Negate(a) => a.ty(),
+            // Real powers of negative numbers are complex
+            Self::Binary(Operator::Pow, _, _) => Type::Complex,
+            Self::Binary(_, a, b) => if a.ty() == Type::Real && b.ty() == Type::Real { Type::Real } else { Type::Complex },
+            Self::Call(Function::Abs | Function::Arg | Function::Re | Function::Im, _) => Type::Real,
+            Self::Call(Function::Conj, a) => a.ty(),
+            Self::Call(_, _) => Type::Complex,
+        }
+    }
+    
+    // Must match wgsl()
+    fn evaluate(&self, z: Complex, c: Complex) -> Complex {
+        match self {
+            Self::Constant(x) => *x,
+            Self::Variable(Variable::Z) => z,
+            Self::Variable(Variable::C) => c,
+            Self::Negate(a) => -a.evaluate(z, c),
+            Self::Binary(operator, a, b) => {
+                let (x, y) = (a.evaluate(z, c), b.evaluate(z, c));
+                match operator {
+                    Operator::Add => x + y,
+                    Operator::Sub => x - y,
+                    Operator::Mul => x * y,
+                    Operator::Div => x / y,
+                    // Real powers go the same way as multibrots, so whole ones are exact
+                    Operator::Pow if b.ty() == Type::Real => {
+                        let power = complex_pow(Vec2(x.re, x.im), y.re);
+                        Complex::new(power.0, power.1)
+                    }
+                    Operator::Pow => x.powc(y),
+                }
+            }
+            Self::Call(function, a) => {
+                let x = a.evaluate(z, c);
+                match function {
+                    Function::Sin => x.sin(),
+                    Function::Cos => x.cos(),
+                    Function::Tan => x.tan(),
+                    Function::Sinh => x.sinh(),
+                    Function::Cosh => x.cosh(),
+                    Function::Tanh => x.tanh(),
+                    Function::Exp => x.exp(),
+                    Function::Log => x.ln(),
+                    Function::Sqrt => x.sqrt(),
+                    Function::Abs => Complex::new(x.abs(), 0.0),
+                    Function::Arg => Complex::new(x.arg(), 0.0),
+                    Function::Re => Complex::new(x.re, 0.0),
+                    Function::Im => Complex::new(x.im, 0.0),
+                    Function::Conj => x.conj(),
+                }
+            }
+        }
+    }
+    
+    // An f32 for real expressions and a vec2<f32> for complex ones. Must match evaluate().
+    fn wgsl(&self) -> String {
+        let real = |expr: &Self| expr.ty() == Type::Real;
+        match self {
+            Self::Constant(x) if x.im == 0.0 => float_literal(x.re),
+            Self::Constant(x) => format!("vec2<f32>({}, {})", float_literal(x.re), float_literal(x.im)),
+            Self::Variable(Variable::Z) => "z".to_string(),
+            Self::Variable(Variable::C) => "c".to_string(),
+            Self::Negate(a) => format!("-({})", a.wgsl()),
+            Self::Binary(operator, a, b) => match operator {
+                Operator::Add | Operator::Sub => {
+                    let symbol = if *operator == Operator::Add { '+' } else { '-' };
+                    if real(a) && real(b) { format!("({} {symbol} {})", a.wgsl(), b.wgsl()) } else { format!("({} {symbol} {})", a.complex_wgsl(), b.complex_wgsl()) }
+                }
+                // Scalars and vectors multiply each other just fine, it's only two complex numbers that need the real thing
+                Operator::Mul if real(a) || real(b) => format!("({} * {})", a.wgsl(), b.wgsl()),
+                Operator::Mul => format!("complex_mul({}, {})", a.wgsl(), b.wgsl()),
+                Operator::Div if real(b) => format!("({} / {})", a.wgsl(), b.wgsl()),
+                Operator::Div => format!("complex_div({}, {})", a.complex_wgsl(), b.wgsl()),
+                Operator::Pow if real(b) => format!("complex_pow({}, {})", a.complex_wgsl(), b.wgsl()),
+                Operator::Pow => format!("complex_powc({}, {})", a.complex_wgsl(), b.wgsl()),
+            }
+            Self::Call(function, a) => match (function, a.ty()) {
+                (Function::Abs, Type::Real) => format!("abs({})", a.wgsl()),
+                (Function::Abs, Type::Complex) => format!("length({})", a.wgsl()),
+                (Function::Arg, _) => format!("complex_arg({})", a.complex_wgsl()),
+                (Function::Re | Function::Conj, Type::Real) => a.wgsl(),
+                (Function::Re, Type::Complex) => format!("({}).x", a.wgsl()),
+                (Function::Im, Type::Real) => "0.0".to_string(),
+                (Function::Im, Type::Complex) => format!("({}).y", a.wgsl()),
+                (Function::Conj, Type::Complex) => format!("conjugate({})", a.wgsl()),
+                (function, _) => {
+                    let name = FUNCTIONS.iter().find(|(_, f)| f == function).unwrap().0;
+                    format!("complex_{name}({})", a.complex_wgsl())
+                }
+            }
+        }
+    }
+    
+    fn complex_wgsl(&self) -> String {
+        match self.ty() {
+            Type::Real => format!("vec2<f32>({}, 0.0)", self.wgsl()),
+            Type::Complex => self.wgsl(),
+        }
+    }
+}
+
+// Numbers too big for an f32 come out as inf, which naga turns away
+fn float_literal(x: f64) -> String {
+    format!("{:?}", x as f32)
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Symbol(char),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Number(x) => write!(f, "{x}"),
+            Self::Name(name) => write!(f, "{name:?}"),
+            Self::Symbol(symbol) => write!(f, "\"{symbol}\""),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> crate::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = s[start..].find(|c: char| !c.is_ascii_digit() && c != '.').map_or(s.len(), |n| start + n);
+            // An e only starts an exponent if there are digits after it, otherwise it's the constant e multiplied on
+            if let Some(exponent) = s[end..].strip_prefix(['e', 'E']) {
+                let unsigned = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+                let digits = unsigned.find(|c: char| !c.is_ascii_digit()).unwrap_or(unsigned.len());
+                if digits > 0 { end = s.len() - unsigned.len() + digits }
+            }
+            while chars.peek().is_some_and(|&(i, _)| i < end) { chars.next(); }
+            let number = &s[start..end];
+            tokens.push(Token::Number(number.parse().map_err(|_| format!("bad number {number:?} in the formula"))?));
+        } else if c.is_alphabetic() {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek().filter(|(_, c)| c.is_alphanumeric() || *c == '_') {
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token::Name(s[start..end].to_string()));
+        } else if "+-*/^(),=".contains(c) {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else {
+            return Err(format!("unexpected \"{c}\" in the formula").into())
+        }
+    }
+    Ok(tokens)
+}
+
+// Recursive descent, loosest binding first. Putting things next to each other multiplies them, so "3z^2" and "2i"
+// work the way they're written.
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    // How deep the tree is where it's being parsed
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+    
+    fn next(&mut self) -> crate::Result<&Token> {
+        let token = self.tokens.get(self.position).ok_or("the formula ends too soon")?;
+        self.position += 1;
+        Ok(token)
+    }
+    
+    fn eat(&mut self, symbol: char) -> bool {
+        let found = self.peek() == Some(&Token::Symbol(symbol));
+        if found { self.position += 1 }
+        found
+    }
+    
+    // Every node that goes under another one goes a level deeper, whether it's nested or just the next term along
+    fn deeper(&mut self) -> crate::Result<()> {
+        self.depth += 1;
+        if self.depth > MAX_FORMULA_DEPTH { return Err(format!("the formula goes more than {MAX_FORMULA_DEPTH} levels deep").into()) }
+        Ok(())
+    }
+    
+    fn sum(&mut self) -> crate::Result<Expr> {
+        let depth = self.depth;
+        let mut expr = self.product()?;
+        loop {
+            let operator = if self.eat('+') { Operator::Add } else if self.eat('-') { Operator::Sub } else { break };
+            self.deeper()?;
+            expr = Expr::Binary(operator, Box::new(expr), Box::new(self.product()?));
+        }
+        self.depth = depth;
+        Ok(expr)
+    }
+    
+    fn product(&mut self) -> crate::Result<Expr> {
+        let depth = self.depth;
+        let mut expr = self.unary()?;
+        loop {
+            let operator = if self.eat('*') {
+                Operator::Mul
+            } else if self.eat('/') {
+                Operator::Div
+            } else if matches!(self.peek(), Some(Token::Number(_) | Token::Name(_) | Token::Symbol('('))) {
+                Operator::Mul
+            } else {
+                break
+            };
+            self.deeper()?;
+            expr = Expr::Binary(operator, Box::new(expr), Box::new(self.unary()?));
+        }
+        self.depth = depth;
+        Ok(expr)
+    }
+    
+    // -z^2 is -(z^2)
+    fn unary(&mut self) -> crate::Result<Expr> {
+        self.deeper()?;
+        let expr = if self.eat('-') {
+            Expr::Negate(Box::new(self.unary()?))
+        } else if self.eat('+') {
+            self.unary()?
+        } else {
+            self.power()?
+        };
+        self.depth -= 1;
+        Ok(expr)
+    }
+    
+    // Right associative, so 2^3^2 is 2^9
+    fn power(&mut self) -> crate::Result<Expr> {
+        let base = self.atom()?;
+        if !self.eat('^') { return Ok(base) }
+        Ok(Expr::Binary(Operator::Pow, Box::new(base), Box::new(self.unary()?)))
+    }
+    
+    fn atom(&mut self) -> crate::Result<Expr> {
+        match self.next()?.clone() {
+            Token::Number(x) => Ok(Expr::Constant(Complex::new(x, 0.0))),
+            Token::Symbol('(') => {
+                let expr = self.sum()?;
+                if !self.eat(')') { return Err("missing \")\" in the formula".into()) }
+                Ok(expr)
+            }
+            Token::Name(name) => {
+                if let Some(&(_, function)) = FUNCTIONS.iter().find(|(n, _)| *n == name) {
+                    if !self.eat('(') { return Err(format!("{name} needs its argument in parentheses").into()) }
+                    let argument = self.sum()?;
+                    if self.peek().is_some_and(|token| *token != Token::Symbol(')')) { return Err(format!("{name} takes one argument").into()) }
+                    if !self.eat(')') { return Err("missing \")\" in the formula".into()) }
+                    return Ok(Expr::Call(function, Box::new(argument)))
+                }
+                match name.as_str() {
+                    "z" => Ok(Expr::Variable(Variable::Z)),
+                    "c" => Ok(Expr::Variable(Variable::C)),
+                    "i" => Ok(Expr::Constant(Complex::new(0.0, 1.0))),
+                    "pi" => Ok(Expr::Constant(Complex::new(std::f64::consts::PI, 0.0))),
+                    "e" => Ok(Expr::Constant(Complex::new(std::f64::consts::E, 0.0))),
+                    _ if self.peek() == Some(&Token::Symbol('(')) => Err(format!("there's no function called {name:?}").into()),
+                    _ => Err(format!("there's no variable called {name:?}, only z and c").into()),
+                }
+            }
+            token => Err(format!("unexpected {token} in the formula").into()),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BigFloat, FloatExp, FractalParams, FractalType, render_cpu};
+    
+    fn close(a: Complex, b: Complex) -> bool {
+        (a - b).abs() < 1e-12 * (1.0 + b.abs())
+    }
+    
+    #[test]
+    fn formulas_parse_and_evaluate() {
+        let (z, c) = (Complex::new(0.3, -0.4), Complex::new(-0.7, 0.2));
+        let i = Complex::new(0.0, 1.0);
+        for (text, expected) in [
+            ("z = z^3 + c*sin(z)", z * z * z + c * z.sin()),
+            ("z^2+c", z * z + c),
+            ("-z^2 + 2i", -(z * z) + i * 2.0),
+            ("3z^2 - z/2", z * z * 3.0 - z * Complex::new(0.5, 0.0)),
+            ("2^3^2", Complex::new(512.0, 0.0)),
+            ("2e-1 z + e", z * 0.2 + Complex::new(std::f64::consts::E, 0.0)),
+            ("exp(log(z)) + sqrt(c)^2", z + c),
+            ("z^c", (c * z.ln()).exp()),
+            ("(re(z) + im(c)) * abs(z) + conj(z) + arg(i) * tanh(c) / cosh(z)", Complex::new((z.re + c.im) * z.abs(), 0.0) + z.conj() + c.tanh() / z.cosh() * (std::f64::consts::PI / 2.0)),
+        ] {
+            let formula = text.parse::<Formula>().unwrap();
+            assert!(close(formula.evaluate(z, c), expected), "{text}: {} vs {expected}", formula.evaluate(z, c));
+        }
+        assert_eq!("z = z^3 + c".parse::<Formula>().unwrap().text, "z^3 + c");
+        
+        for bad in ["z^", "sin(z", "sin z", "sin(z, c)", "foo(z)", "x + c", "z + c)", "z = ", "z == c", "z $ c", "1..2"] {
+            assert!(bad.parse::<Formula>().is_err(), "{bad}");
+        }
+        
+        // Deep enough to run out of stack if nothing stopped it
+        let deep = 100000;
+        for bad in [format!("{}z{}", "(".repeat(deep), ")".repeat(deep)), format!("{}z", "-".repeat(deep)), format!("z{}", "^z".repeat(deep)), "z+".repeat(deep) + "c"] {
+            assert!(bad.parse::<Formula>().is_err(), "{}...", &bad[..10]);
+        }
+        let nested = format!("{}z{}", "sin(".repeat(20), ")".repeat(20));
+        assert!(close(nested.parse::<Formula>().unwrap().evaluate(z, c), (0..20).fold(z, |z, _| z.sin())));
+    }
+    
+    #[test]
+    fn formulas_compile_to_valid_wgsl() {
+        let shader = concat!(include_str!("df64.wgsl"), include_str!("shader.wgsl"));
+        validate_wgsl(shader).unwrap();
+        for text in ["z^2 + c", "-z", "re(z) * im(c) - abs(-2)", "conj(z)^2.5 + c / (1 + z^-2)", "sin(z) + cos(z) + tan(z) + sinh(z) + cosh(z) + tanh(z)", "exp(z) * log(c) + sqrt(z)^c + arg(2) + abs(z)"] {
+            let spliced = text.parse::<Formula>().unwrap().splice(shader);
+            assert!(spliced.contains("fn custom_step"));
+            validate_wgsl(&spliced).unwrap_or_else(|e| panic!("{text}: {e}"));
+        }
+        // Fine as an f64, too big for the shader's f32s
+        assert!(validate_wgsl(&"z + 1e39".parse::<Formula>().unwrap().splice(shader)).is_err());
+    }
+    
+    #[test]
+    fn quadratic_formula_draws_the_mandelbrot_set() {
+        let mandelbrot = FractalParams { center: Vec2(BigFloat::from_f64(-0.6, 2), BigFloat::from_f64(0.4, 2)), scale: FloatExp::from_f64(0.5), max_iterations: 100, ..Default::default() };
+        let custom = FractalParams { fractal: FractalType::Custom, formula: "z = z*z + c".parse().unwrap(), ..mandelbrot.clone() };
+        let (expected, actual) = (render_cpu(&mandelbrot, 24, 16), render_cpu(&custom, 24, 16));
+        for (expected, actual) in expected.samples.iter().zip(&actual.samples) {
+            assert_eq!(expected.escaped, actual.escaped);
+            // Interior points get caught by different checks, so only escape times line up
+            if expected.escaped { assert_eq!(expected.iterations, actual.iterations) }
+        }
+    }
+}
@@ -0,0 +1,1267 @@
+use crate::{BigFloat, Complex, FloatExp, Formula, Vec2, polynomial_from_roots, polynomial_roots};
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FractalParams {
+    // Arbitrary precision so deep zoom locations don't get rounded to wherever an f64 can land
+    pub center: Vec2<BigFloat>,
+    // Half the height of the view in the complex plane, which goes well past the bottom of f64's range at deep zoom
+    pub scale: FloatExp,
+    pub max_iterations: u32,
+    pub bailout: f64,
+    pub fractal: FractalType,
+    // d in z^d + c for multibrots
+    pub exponent: f64,
+    // The polynomial newton basins are for, highest power first, and its roots to tell the basins apart by
+    pub coefficients: Vec<Complex>,
+    pub roots: Vec<Complex>,
+    // Newton steps get multiplied by this. Anything other than 1 wanders off towards nova territory.
+    pub relaxation: Complex,
+    // What custom fractals iterate
+    pub formula: Formula,
+    // Which of a and b the logistic map uses at each step of a lyapunov fractal, like "AABAB"
+    pub sequence: String,
+    // Some(c) renders the julia set for c instead of the mandelbrot set
+    pub julia: Option<Vec2<f64>>,
+    pub color_mode: ColorMode,
+    // Points that never escape get colored separately from the ones that do
+    pub interior: InteriorMode,
+    // How far out from the boundary distance estimate coloring fades to white, in pixels
+    pub line_thickness: f64,
+    pub trap: OrbitTrap,
+}
+
+// Which iteration gets run. Each one has a julia version too.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FractalType {
+    Mandelbrot,
+    // z^d + c for any real d
+    Multibrot,
+    // The quadratic map with abs or conjugates thrown in
+    BurningShip,
+    Tricorn,
+    Celtic,
+    Buffalo,
+    Perpendicular,
+    // z^2 + Re(c) + Im(c)·z_prev
+    Phoenix,
+    // Rational maps from a model of magnetism, which can converge to 1 as well as escaping
+    MagnetI,
+    MagnetII,
+    // Basins of Newton's method on a polynomial
+    Newton,
+    // Newton's method plus c each step
+    Nova,
+    // Whatever formula got typed in
+    Custom,
+    // Lyapunov exponents of the logistic map, with r switching between the a and b axes
+    Lyapunov,
+}
+
+// Biggest polynomial the shader has room for
+pub const MAX_NEWTON_DEGREE: usize = 8;
+
+// Orbits that settle down count as converged once their steps get this short, or once magnets get this close to 1.
+// Must match CONVERGENCE_TOLERANCE in shader.wgsl.
+const CONVERGENCE_TOLERANCE: f64 = 1e-4;
+// Magnet orbits wander well past 2 on their way to converging, and nova orbits hardly ever escape at all
+const MAGNET_BAILOUT: f64 = 100.0;
+const NOVA_BAILOUT: f64 = 1e10;
+
+// The sequence goes to the shader as the bits of a u32, B for 1
+pub const MAX_SEQUENCE_LENGTH: usize = 32;
+// Iterations the logistic map gets to settle in before the exponent starts adding up. Must match LYAPUNOV_WARMUP in
+// shader.wgsl.
+const LYAPUNOV_WARMUP: u32 = 64;
+// Keeps superstable points, where the derivative is exactly 0, from taking the log to -infinity
+const LYAPUNOV_FLOOR: f64 = 1e-30;
+
+impl FractalType {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Mandelbrot => Self::Multibrot,
+            Self::Multibrot => Self::BurningShip,
+            Self::BurningShip => Self::Tricorn,
+            Self::Tricorn => Self::Celtic,
+            Self::Celtic => Self::Buffalo,
+            Self::Buffalo => Self::Perpendicular,
+            Self::Perpendicular => Self::Phoenix,
+            Self::Phoenix => Self::MagnetI,
+            Self::MagnetI => Self::MagnetII,
+            Self::MagnetII => Self::Newton,
+            Self::Newton => Self::Nova,
+            Self::Nova => Self::Custom,
+            Self::Custom => Self::Lyapunov,
+            Self::Lyapunov => Self::Mandelbrot,
+        }
+    }
+}
+
+// How an escaped point's orbit turns into a palette index
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ColorMode {
+    // Whole iteration counts, so it comes out in bands
+    Banded,
+    // Normalized iteration count, continuous across the bands
+    Smooth,
+    // Exterior distance estimate, which draws the boundary as lines of an even thickness
+    Distance,
+}
+
+impl ColorMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Banded => Self::Smooth,
+            Self::Smooth => Self::Distance,
+            Self::Distance => Self::Banded,
+        }
+    }
+}
+
+// What points inside the set get colored by
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InteriorMode {
+    Black,
+    // |z| and arg z after the last iteration
+    Magnitude,
+    Angle,
+    // The period of the cycle the orbit fell into
+    Period,
+    // Interior distance estimate, drawn the same as the exterior one. Only the mandelbrot set's parameter space has one.
+    Distance,
+    // Which iteration set the last record for getting close to 0
+    AtomDomain,
+}
+
+// Newton steps to get from wherever cycle detection noticed the cycle to the cycle itself. Must match
+// INTERIOR_NEWTON_STEPS in shader.wgsl.
+const INTERIOR_NEWTON_STEPS: u32 = 4;
+
+impl InteriorMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Black => Self::Magnitude,
+            Self::Magnitude => Self::Angle,
+            Self::Angle => Self::Period,
+            Self::Period => Self::Distance,
+            Self::Distance => Self::AtomDomain,
+            Self::AtomDomain => Self::Black,
+        }
+    }
+    
+    // Anything that looks at the orbit can't take the closed form shortcut for the cardioid and period 2 bulb. Must
+    // match interior_needs_orbit() in shader.wgsl.
+    fn needs_orbit(self) -> bool {
+        !matches!(self, Self::Black | Self::Period)
+    }
+    
+    // And final |z| and arg z are of the very last iterate, so those can't stop as soon as they find a cycle either.
+    // Must match interior_needs_last_z() in shader.wgsl.
+    fn needs_last_z(self) -> bool {
+        matches!(self, Self::Magnitude | Self::Angle)
+    }
+}
+
+// A shape in the plane that escaping orbits get lit up by the closer they come to it
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OrbitTrap {
+    // None for no trap at all
+    pub shape: Option<TrapShape>,
+    pub center: Vec2<f64>,
+    // Only circles have a radius. Images go this far up and down from the center.
+    pub radius: f64,
+    // Which way lines, crosses and images point, in degrees
+    pub angle: f64,
+    // How far from the trap orbits have to stay to go dark. The light falls off exponentially with distance.
+    pub width: f64,
+    // Width over height of the image for image traps
+    pub aspect: f64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TrapShape {
+    Point,
+    Line,
+    Circle,
+    // Two lines at right angles
+    Cross,
+    // A rectangle with a picture in it, which the first point of the orbit to land in picks a color from
+    Image,
+}
+
+impl Default for OrbitTrap {
+    fn default() -> Self {
+        Self { shape: None, center: Vec2(0.0, 0.0), radius: 1.0, angle: 0.0, width: 0.1, aspect: 1.0 }
+    }
+}
+
+impl OrbitTrap {
+    pub fn next_shape(&mut self) {
+        self.shape = match self.shape {
+            None => Some(TrapShape::Point),
+            Some(TrapShape::Point) => Some(TrapShape::Line),
+            Some(TrapShape::Line) => Some(TrapShape::Circle),
+            Some(TrapShape::Circle) => Some(TrapShape::Cross),
+            Some(TrapShape::Cross) => Some(TrapShape::Image),
+            Some(TrapShape::Image) => None,
+        };
+    }
+    
+    // "re im", then optionally the radius, angle and width, with anything left out staying as it was
+    pub fn set(&mut self, text: &str) -> crate::Result<()> {
+        let numbers = text.split([' ', ',']).filter(|s| !s.is_empty()).map(str::parse).collect::<Result<Vec<f64>, _>>()?;
+        let [re, im, rest @ ..] = &numbers[..] else { return Err(format!("expected \"re im [radius] [angle] [width]\", got {text:?}").into()) };
+        if rest.len() > 3 { return Err(format!("expected at most 5 numbers, got {}", numbers.len()).into()) }
+        let radius = rest.first().copied().unwrap_or(self.radius);
+        let width = rest.get(2).copied().unwrap_or(self.width);
+        if radius < 0.0 || width <= 0.0 { return Err("the radius can't be negative and the width has to be positive".into()) }
+        self.center = Vec2(*re, *im);
+        self.radius = radius;
+        self.angle = rest.get(1).copied().unwrap_or(self.angle);
+        self.width = width;
+        Ok(())
+    }
+    
+    // In the trap's own frame, where its lines run along the axes
+    fn local(&self, z: Vec2<f64>) -> Vec2<f64> {
+        let offset = Vec2(z.0 - self.center.0, z.1 - self.center.1);
+        let (sin, cos) = self.angle.to_radians().sin_cos();
+        Vec2(offset.0 * cos + offset.1 * sin, offset.1 * cos - offset.0 * sin)
+    }
+    
+    // Where z is in the image as texture coordinates, from 0 to 1 with y going down, if it's in the image at all. Must
+    // match trap_image_position() in shader.wgsl.
+    pub fn image_position(&self, z: Vec2<f64>) -> Option<Vec2<f64>> {
+        let local = self.local(z);
+        let position = Vec2((local.0 / self.radius / self.aspect + 1.0) * 0.5, (1.0 - local.1 / self.radius) * 0.5);
+        if (0.0..=1.0).contains(&position.0) && (0.0..=1.0).contains(&position.1) { Some(position) } else { None }
+    }
+    
+    // How far z is from the trap. Must match trap_distance() in shader.wgsl.
+    pub fn distance(&self, z: Vec2<f64>) -> f64 {
+        let offset = Vec2(z.0 - self.center.0, z.1 - self.center.1);
+        let local = self.local(z);
+        match self.shape {
+            None | Some(TrapShape::Image) => f64::INFINITY,
+            Some(TrapShape::Point) => offset.0.hypot(offset.1),
+            Some(TrapShape::Line) => local.1.abs(),
+            Some(TrapShape::Circle) => (offset.0.hypot(offset.1) - self.radius).abs(),
+            Some(TrapShape::Cross) => local.0.abs().min(local.1.abs()),
+        }
+    }
+    
+    // What the palette color gets multiplied by, for an orbit that came within distance of the trap. Must match
+    // trap_brightness() in shader.wgsl.
+    pub fn brightness(&self, distance: f32) -> f32 {
+        if matches!(self.shape, None | Some(TrapShape::Image)) { return 1.0 }
+        (-distance / self.width as f32).exp()
+    }
+}
+
+impl Default for FractalParams {
+    fn default() -> Self {
+        Self {
+            center: Vec2(BigFloat::from_f64(-0.5, 2), BigFloat::from_f64(0.0, 2)),
+            scale: FloatExp::from_f64(1.25),
+            max_iterations: 256,
+            bailout: 2.0,
+            fractal: FractalType::Mandelbrot,
+            exponent: 2.0,
+            // z^3 - 1
+            coefficients: vec![Complex::ONE, Complex::ZERO, Complex::ZERO, -Complex::ONE],
+            roots: [0.0, 1.0, 2.0].map(|k: f64| {
+                let angle = std::f64::consts::TAU * k / 3.0;
+                Complex::new(angle.cos(), angle.sin())
+            }).to_vec(),
+            relaxation: Complex::ONE,
+            formula: Formula::default(),
+            sequence: "AB".to_string(),
+            julia: None,
+            color_mode: ColorMode::Banded,
+            interior: InteriorMode::Black,
+            line_thickness: 2.0,
+            trap: OrbitTrap::default(),
+        }
+    }
+}
+
+impl FractalParams {
+    pub fn center_f64(&self) -> Vec2<f64> {
+        Vec2(self.center.0.to_f64(), self.center.1.to_f64())
+    }
+    
+    // Pixel coordinates are measured from the top left corner, same as the fragment shader's clip position.
+    pub fn pixel_offset(&self, x: f64, y: f64, width: u32, height: u32) -> Vec2<FloatExp> {
+        let aspect = width as f64 / height as f64;
+        Vec2(
+            self.scale * ((x / width as f64 * 2.0 - 1.0) * aspect),
+            self.scale * -(y / height as f64 * 2.0 - 1.0),
+        )
+    }
+    
+    pub fn pixel_to_complex(&self, x: f64, y: f64, width: u32, height: u32) -> Vec2<f64> {
+        let center = self.center_f64();
+        let offset = self.pixel_offset(x, y, width, height);
+        Vec2(center.0 + offset.0.to_f64(), center.1 + offset.1.to_f64())
+    }
+    
+    // Moves the center by an offset in the complex plane, adding precision first if the view has zoomed past what it has
+    pub fn shift_center(&mut self, offset: Vec2<FloatExp>) {
+        let limbs = BigFloat::limbs_for_scale(self.scale).max(self.center.0.fraction_limbs());
+        self.center = Vec2(
+            &self.center.0 + &BigFloat::from_float_exp(offset.0, limbs),
+            &self.center.1 + &BigFloat::from_float_exp(offset.1, limbs),
+        );
+    }
+    
+    // "re im scale", written out in full so it reads back to exactly the same view, as long as the center has no more
+    // precision than the scale needs
+    pub fn location(&self) -> String {
+        format!("{} {} {:e}", self.center.0, self.center.1, self.scale)
+    }
+    
+    pub fn set_location(&mut self, location: &str) -> crate::Result<()> {
+        let [re, im, scale] = location.split_whitespace().collect::<Vec<_>>()[..] else { return Err(format!("expected \"re im scale\", got {location:?}").into()) };
+        let scale = scale.parse::<FloatExp>()?;
+        if scale <= FloatExp::ZERO { return Err(format!("scale {scale:e} has to be positive").into()) }
+        // Every decimal digit turns into precision, so this cuts it back to what the view needs or every reference orbit
+        // from here on would be dragging the extra limbs along
+        let limbs = BigFloat::limbs_for_scale(scale);
+        self.center = Vec2(re.parse::<BigFloat>()?.with_precision(limbs), im.parse::<BigFloat>()?.with_precision(limbs));
+        self.scale = scale;
+        Ok(())
+    }
+    
+    pub fn name(&self) -> String {
+        match self.fractal {
+            FractalType::Mandelbrot => "Mandelbrot".to_string(),
+            FractalType::Multibrot => format!("Multibrot z^{} + c", self.exponent),
+            FractalType::BurningShip => "Burning Ship".to_string(),
+            FractalType::Tricorn => "Tricorn".to_string(),
+            FractalType::Celtic => "Celtic".to_string(),
+            FractalType::Buffalo => "Buffalo".to_string(),
+            FractalType::Perpendicular => "Perpendicular".to_string(),
+            FractalType::Phoenix => "Phoenix".to_string(),
+            FractalType::MagnetI => "Magnet I".to_string(),
+            FractalType::MagnetII => "Magnet II".to_string(),
+            FractalType::Custom => format!("z = {}", self.formula.text),
+            FractalType::Lyapunov => format!("Lyapunov {}", self.sequence),
+            FractalType::Newton | FractalType::Nova => {
+                let name = if self.fractal == FractalType::Newton { "Newton" } else { "Nova" };
+                let relaxation = if self.relaxation == Complex::ONE { String::new() } else { format!(", relaxation {}", self.relaxation) };
+                format!("{name} p(z) = {}{relaxation}", polynomial_to_string(&self.coefficients))
+            }
+        }
+    }
+    
+    // Either coefficients, highest power first, like "1 0 0 -1" for z^3 - 1, or the roots themselves, like "roots 1 i -1 -i"
+    pub fn set_polynomial(&mut self, text: &str) -> crate::Result<()> {
+        let (from_roots, numbers) = match text.trim().strip_prefix("roots") {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        let numbers = numbers.split([' ', ',']).filter(|s| !s.is_empty()).map(str::parse).collect::<crate::Result<Vec<Complex>>>()?;
+        let leading = numbers.iter().position(|c| *c != Complex::ZERO).unwrap_or(numbers.len());
+        // Checked before solving, since finding the roots of some huge degree could take forever
+        let degree = if from_roots { numbers.len() } else { (numbers.len() - leading).saturating_sub(1) };
+        if degree > MAX_NEWTON_DEGREE { return Err(format!("degree {degree} is more than the {MAX_NEWTON_DEGREE} there's room for").into()) }
+        let (coefficients, roots) = if from_roots {
+            (polynomial_from_roots(&numbers), numbers)
+        } else {
+            let roots = polynomial_roots(&numbers)?;
+            (numbers[leading..].to_vec(), roots)
+        };
+        if roots.is_empty() { return Err("the polynomial needs at least one root".into()) }
+        self.coefficients = coefficients;
+        self.roots = roots;
+        Ok(())
+    }
+    
+    pub fn set_sequence(&mut self, text: &str) -> crate::Result<()> {
+        let sequence = text.trim().to_uppercase();
+        if sequence.is_empty() || sequence.len() > MAX_SEQUENCE_LENGTH { return Err(format!("the sequence needs 1 to {MAX_SEQUENCE_LENGTH} letters").into()) }
+        if let Some(c) = sequence.chars().find(|&c| c != 'A' && c != 'B') { return Err(format!("{c:?} isn't A or B").into()) }
+        self.sequence = sequence;
+        Ok(())
+    }
+    
+    // The sequence as the shader takes it, one bit per letter starting from the lowest
+    pub fn sequence_bits(&self) -> u32 {
+        self.sequence.chars().enumerate().filter(|&(_, c)| c == 'B').fold(0, |bits, (i, _)| bits | 1 << i)
+    }
+    
+    // Lyapunov space is a different plane entirely, so going in or out of it starts over at that plane's usual view
+    pub fn set_fractal(&mut self, fractal: FractalType) {
+        if (fractal == FractalType::Lyapunov) != (self.fractal == FractalType::Lyapunov) {
+            let (center, scale) = match (fractal, self.julia) {
+                (FractalType::Lyapunov, _) => ((3.0, 3.0), 1.0),
+                (_, Some(_)) => ((0.0, 0.0), 1.5),
+                (_, None) => ((-0.5, 0.0), 1.25),
+            };
+            self.center = Vec2(BigFloat::from_f64(center.0, 2), BigFloat::from_f64(center.1, 2));
+            self.scale = FloatExp::from_f64(scale);
+        }
+        self.fractal = fractal;
+    }
+    
+    // Only plain mandelbrot iterations have df64 and perturbation versions, everything else is stuck with f32 on the gpu
+    pub fn supports_deep_zoom(&self) -> bool {
+        self.fractal == FractalType::Mandelbrot
+    }
+    
+    fn escape_radius(&self) -> f64 {
+        match self.fractal {
+            FractalType::MagnetI | FractalType::MagnetII => self.bailout.max(MAGNET_BAILOUT),
+            FractalType::Nova => NOVA_BAILOUT,
+            _ => self.bailout,
+        }
+    }
+    
+    // Magnets converge to the fixed point at 1, novas to wherever their steps die down. Must match converged() in
+    // shader.wgsl.
+    fn converged(&self, z: Vec2<f64>, previous: Vec2<f64>) -> bool {
+        let offset = match self.fractal {
+            FractalType::MagnetI | FractalType::MagnetII => Vec2(z.0 - 1.0, z.1),
+            FractalType::Nova => Vec2(z.0 - previous.0, z.1 - previous.1),
+            _ => return false,
+        };
+        offset.0 * offset.0 + offset.1 * offset.1 < CONVERGENCE_TOLERANCE * CONVERGENCE_TOLERANCE
+    }
+    
+    // The rational maps and typed-in formulas don't get derivatives tracked, so they can't do distance estimates
+    pub fn has_distance_estimate(&self) -> bool {
+        !matches!(self.fractal, FractalType::MagnetI | FractalType::MagnetII | FractalType::Nova | FractalType::Custom)
+    }
+    
+    // The relaxed Newton step for the polynomial, R·p(z)/p'(z)
+    fn newton_step(&self, z: Complex) -> Complex {
+        // Horner's method for p and p' at once
+        let (p, derivative) = self.coefficients.iter().fold((Complex::ZERO, Complex::ZERO), |(p, derivative), &c| (p * z + c, derivative * z + p));
+        self.relaxation * (p / derivative)
+    }
+    
+    // How fast escaping orbits grow, |z| -> |z|^degree. Anything under 1 doesn't grow them steadily, so it gets banded.
+    fn escape_degree(&self) -> f64 {
+        match self.fractal {
+            FractalType::Multibrot => self.exponent,
+            _ => 2.0,
+        }
+    }
+    
+    // One iteration of z and the derivative along with it. The abs and conjugate variants aren't complex differentiable,
+    // so for those the derivative is along the real axis, pushed through the step's jacobian. Must match fractal_step()
+    // and derivative_step() in shader.wgsl.
+    fn step(&self, z: Vec2<f64>, derivative: Vec2<f64>, c: Vec2<f64>, pixel_size: f64) -> (Vec2<f64>, Vec2<f64>) {
+        let double = |z: Vec2<f64>| Vec2(2.0 * z.0, 2.0 * z.1);
+        let abs = |z: Vec2<f64>| Vec2(z.0.abs(), z.1.abs());
+        // Folding a coordinate with abs flips the sign of its derivative wherever the coordinate was negative
+        let signs = |z: Vec2<f64>, w: Vec2<f64>| Vec2(sign(z.0) * w.0, sign(z.1) * w.1);
+        let square = complex_mul(z, z);
+        let (z, mut derivative) = match self.fractal {
+            FractalType::Mandelbrot => (square, double(complex_mul(z, derivative))),
+            FractalType::Multibrot => {
+                let factor = complex_pow(z, self.exponent - 1.0);
+                (complex_pow(z, self.exponent), complex_mul(Vec2(self.exponent * factor.0, self.exponent * factor.1), derivative))
+            }
+            FractalType::BurningShip => (complex_mul(abs(z), abs(z)), double(complex_mul(abs(z), signs(z, derivative)))),
+            FractalType::Tricorn => (conjugate(square), conjugate(double(complex_mul(z, derivative)))),
+            FractalType::Celtic => {
+                let product = double(complex_mul(z, derivative));
+                (Vec2(square.0.abs(), square.1), Vec2(sign(square.0) * product.0, product.1))
+            }
+            FractalType::Buffalo => {
+                let (product, folded) = (double(complex_mul(z, derivative)), double(complex_mul(abs(z), signs(z, derivative))));
+                (Vec2(square.0.abs(), -square.1.abs()), Vec2(sign(square.0) * product.0, -folded.1))
+            }
+            FractalType::Perpendicular => {
+                let folded = Vec2(z.0.abs(), z.1);
+                (conjugate(complex_mul(folded, folded)), conjugate(double(complex_mul(folded, Vec2(sign(z.0) * derivative.0, derivative.1)))))
+            }
+            FractalType::MagnetI | FractalType::MagnetII => {
+                let (z, c, one) = (Complex::new(z.0, z.1), Complex::new(c.0, c.1), Complex::ONE);
+                let ratio = if self.fractal == FractalType::MagnetI {
+                    (z * z + c - one) / (z * 2.0 + c - one * 2.0)
+                } else {
+                    let (c1, c2) = (c - one, c - one * 2.0);
+                    (z * z * z + c1 * z * 3.0 + c1 * c2) / (z * z * 3.0 + c2 * z * 3.0 + c1 * c2 + one)
+                };
+                let z = ratio * ratio;
+                (Vec2(z.re, z.im), Vec2(0.0, 0.0))
+            }
+            FractalType::Nova => {
+                let z = Complex::new(z.0, z.1);
+                let z = z - self.newton_step(z);
+                (Vec2(z.re, z.im), Vec2(0.0, 0.0))
+            }
+            FractalType::Custom => {
+                let z = self.formula.evaluate(Complex::new(z.0, z.1), Complex::new(c.0, c.1));
+                (Vec2(z.re, z.im), Vec2(0.0, 0.0))
+            }
+            // Phoenix's z^2 here, and the rest gets added on in sample() where the previous z is
+            FractalType::Phoenix => (square, double(complex_mul(z, derivative))),
+            FractalType::Newton => unreachable!("newton basins run their own loop in sample_newton()"),
+            FractalType::Lyapunov => unreachable!("lyapunov fractals run their own loop in sample_lyapunov()"),
+        };
+        // Plus dc for the mandelbrot set
+        if self.julia.is_none() { derivative.0 += pixel_size }
+        let z = match self.fractal {
+            // c's imaginary part is the coefficient for the previous z instead
+            FractalType::Phoenix => Vec2(z.0 + c.0, z.1),
+            // Magnets already have c inside the fraction, and formulas put it wherever they like
+            FractalType::MagnetI | FractalType::MagnetII | FractalType::Custom => z,
+            _ => Vec2(z.0 + c.0, z.1 + c.1),
+        };
+        (z, derivative)
+    }
+    
+    pub fn pixel_size(&self, height: u32) -> FloatExp {
+        self.scale * (2.0 / height as f64)
+    }
+    
+    // The derivative gets tracked multiplied by the pixel size, same as the shader, so the distance comes out in pixels
+    pub fn sample(&self, point: Vec2<f64>, pixel_size: f64) -> Sample {
+        // Newton basins are in the z plane and lyapunov fractals in the a-b plane, so there's no julia version of either
+        if self.fractal == FractalType::Newton { return self.sample_newton(point) }
+        if self.fractal == FractalType::Lyapunov { return self.sample_lyapunov(point) }
+        
+        let (mut z, c, mut derivative) = match self.julia {
+            Some(c) => (point, c, Vec2(pixel_size, 0.0)),
+            // Parameter space orbits start at a root of the polynomial by convention. That's only a critical point of the step
+            // when the relaxation is 1.
+            None if self.fractal == FractalType::Nova => {
+                let root = self.roots.first().copied().unwrap_or_default();
+                (Vec2(root.re, root.im), point, Vec2(0.0, 0.0))
+            }
+            None => (Vec2(0.0, 0.0), point, Vec2(0.0, 0.0)),
+        };
+        
+        let period = if self.julia.is_none() && self.fractal == FractalType::Mandelbrot { known_period(c) } else { 0 };
+        if period != 0 && !self.interior.needs_orbit() { return Sample::interior(period) }
+        
+        let bailout_squared = self.escape_radius() * self.escape_radius();
+        let mut periodicity = Periodicity::new(z, 1e-3 * pixel_size);
+        let (mut previous, mut previous_derivative) = (Vec2(0.0, 0.0), Vec2(0.0, 0.0));
+        // The starting z doesn't count, or every mandelbrot orbit would be right on top of a trap at 0
+        let mut trap = f64::INFINITY;
+        let mut trap_image = None;
+        // Must match orbit_update() in shader.wgsl
+        let (mut closest, mut atom) = (f64::INFINITY, 0);
+        let mut n = 0;
+        while n < self.max_iterations {
+            if z.0 * z.0 + z.1 * z.1 > bailout_squared { break }
+            let (mut next, mut next_derivative) = self.step(z, derivative, c, pixel_size);
+            if self.fractal == FractalType::Phoenix {
+                next = Vec2(next.0 + c.1 * previous.0, next.1 + c.1 * previous.1);
+                next_derivative = Vec2(next_derivative.0 + c.1 * previous_derivative.0, next_derivative.1 + c.1 * previous_derivative.1);
+            }
+            (previous, previous_derivative, z, derivative) = (z, derivative, next, next_derivative);
+            n += 1;
+            if z.0 * z.0 + z.1 * z.1 < closest { (closest, atom) = (z.0 * z.0 + z.1 * z.1, n) }
+            match self.trap.shape {
+                // Only the first point in the image counts
+                Some(TrapShape::Image) if trap_image.is_none() => trap_image = self.trap.image_position(z),
+                Some(TrapShape::Image) => (),
+                Some(_) => trap = trap.min(self.trap.distance(z)),
+                None => (),
+            }
+            // Must match color_value() in shader.wgsl, which gives converged orbits the other half of the palette
+            if self.converged(z, previous) {
+                return Sample { iterations: n, value: n as f32 + 25.0, distance: 0.0, period: 0, escaped: n < self.max_iterations, converged: true, trap: trap as f32, trap_image, z, atom }
+            }
+            if let Some(period) = periodicity.check(z) && !self.interior.needs_last_z() {
+                let distance = if self.interior == InteriorMode::Distance { self.interior_distance(z, c, period) / pixel_size } else { 0.0 };
+                return Sample { distance: distance as f32, z, atom, ..Sample::interior(period) }
+            }
+        }
+        
+        // Must match color_value() in shader.wgsl
+        let value = match self.color_mode {
+            ColorMode::Smooth if self.escape_degree() > 1.0 => n as f64 + 1.0 - z.0.hypot(z.1).ln().ln() / self.escape_degree().ln(),
+            _ => n as f64,
+        };
+        let distance = z.0.hypot(z.1) * z.0.hypot(z.1).ln() / derivative.0.hypot(derivative.1);
+        Sample { iterations: n, value: value as f32, distance: distance as f32, period: 0, escaped: n < self.max_iterations, converged: false, trap: trap as f32, trap_image, z, atom }
+    }
+    
+    // Interior distance estimate, from the derivatives of the period p map at the attracting cycle. z only has to be
+    // close to the cycle, Newton's method takes it the rest of the way. Only the mandelbrot set's parameter space has
+    // one, and it's 0 everywhere else. Must match interior_distance() in shader.wgsl.
+    pub fn interior_distance(&self, z: Vec2<f64>, c: Vec2<f64>, period: u32) -> f64 {
+        if self.julia.is_some() || self.fractal != FractalType::Mandelbrot { return 0.0 }
+        let (c, one) = (Complex::new(c.0, c.1), Complex::ONE);
+        let mut cycle = Complex::new(z.0, z.1);
+        for _ in 0..INTERIOR_NEWTON_STEPS {
+            let (mut z, mut dz) = (cycle, one);
+            for _ in 0..period {
+                dz = z * dz * 2.0;
+                z = z * z + c;
+            }
+            cycle = cycle - (z - cycle) / (dz - one);
+        }
+        
+        let (mut z, mut dz, mut dc, mut dzdz, mut dcdz) = (cycle, one, Complex::ZERO, Complex::ZERO, Complex::ZERO);
+        for _ in 0..period {
+            dcdz = (z * dcdz + dc * dz) * 2.0;
+            dzdz = (z * dzdz + dz * dz) * 2.0;
+            dc = z * dc * 2.0 + one;
+            dz = z * dz * 2.0;
+            z = z * z + c;
+        }
+        ((1.0 - dz.norm_squared()) / (dcdz + dzdz * dc / (one - dz)).abs()).max(0.0)
+    }
+    
+    // Must match iterate_newton() and newton_value() in shader.wgsl
+    fn sample_newton(&self, point: Vec2<f64>) -> Sample {
+        let mut z = Complex::new(point.0, point.1);
+        let mut n = 0;
+        while n < self.max_iterations {
+            let step = self.newton_step(z);
+            z = z - step;
+            if step.norm_squared() < CONVERGENCE_TOLERANCE * CONVERGENCE_TOLERANCE { break }
+            n += 1;
+        }
+        
+        let nearest = (0..self.roots.len()).min_by(|&i, &j| (z - self.roots[i]).norm_squared().total_cmp(&(z - self.roots[j]).norm_squared())).unwrap_or(0);
+        let value = 50.0 * nearest as f32 / self.roots.len().max(1) as f32;
+        Sample { iterations: n, value, distance: 0.0, period: 0, escaped: n < self.max_iterations, converged: false, trap: f32::INFINITY, trap_image: None, z: Vec2(z.re, z.im), atom: 0 }
+    }
+    
+    // The average of ln|r(1 - 2x)|, the logistic map's log derivative, along the orbit of x = 0.5. It comes out negative
+    // where the orbit settles into a cycle and positive where it's chaotic. Orbits that leave [0, 1] head off to
+    // infinity and don't get an exponent at all. Must match lyapunov_exponent() in shader.wgsl.
+    pub fn lyapunov_exponent(&self, a: f64, b: f64) -> Option<f64> {
+        let bits = self.sequence_bits();
+        let length = self.sequence.len().max(1) as u32;
+        let mut x = 0.5;
+        let mut total = 0.0;
+        for n in 0..LYAPUNOV_WARMUP + self.max_iterations {
+            let r = if bits >> (n % length) & 1 == 1 { b } else { a };
+            if n >= LYAPUNOV_WARMUP { total += (r * (1.0 - 2.0 * x)).abs().max(LYAPUNOV_FLOOR).ln() }
+            x = r * x * (1.0 - x);
+            if !(0.0..=1.0).contains(&x) { return None }
+        }
+        Some(total / self.max_iterations.max(1) as f64)
+    }
+    
+    fn sample_lyapunov(&self, point: Vec2<f64>) -> Sample {
+        match self.lyapunov_exponent(point.0, point.1) {
+            Some(exponent) => Sample { iterations: self.max_iterations, value: exponent as f32, distance: 0.0, period: 0, escaped: true, converged: false, trap: f32::INFINITY, trap_image: None, z: Vec2(0.0, 0.0), atom: 0 },
+            None => Sample::interior(0),
+        }
+    }
+}
+
+fn complex_mul(a: Vec2<f64>, b: Vec2<f64>) -> Vec2<f64> {
+    Vec2(a.0 * b.0 - a.1 * b.1, a.0 * b.1 + a.1 * b.0)
+}
+
+fn conjugate(z: Vec2<f64>) -> Vec2<f64> {
+    Vec2(z.0, -z.1)
+}
+
+// Same as wgsl's sign(), which is 0 at 0 unlike f64::signum
+fn sign(x: f64) -> f64 {
+    if x == 0.0 { 0.0 } else { x.signum() }
+}
+
+// z^d, by repeated multiplication for whole powers and in polar form otherwise, or for ones too big to loop over. 0^d
+// comes out 0 even for negative d, so mandelbrot orbits still go 0, c, ... Must match complex_pow() in shader.wgsl.
+pub fn complex_pow(z: Vec2<f64>, d: f64) -> Vec2<f64> {
+    if z == Vec2(0.0, 0.0) { return z }
+    if d != 0.0 && d == d.round() && d.abs() <= 64.0 {
+        let power = (1..d.abs() as i32).fold(z, |result, _| complex_mul(result, z));
+        if d > 0.0 { return power }
+        let length_squared = power.0 * power.0 + power.1 * power.1;
+        return Vec2(power.0 / length_squared, -power.1 / length_squared)
+    }
+    let r = z.0.hypot(z.1).powf(d);
+    let theta = z.1.atan2(z.0) * d;
+    Vec2(r * theta.cos(), r * theta.sin())
+}
+
+fn polynomial_to_string(coefficients: &[Complex]) -> String {
+    let degree = coefficients.len().saturating_sub(1);
+    let terms = coefficients.iter().enumerate().filter(|(_, c)| **c != Complex::ZERO).map(|(i, c)| {
+        let power = degree - i;
+        let coefficient = match (c.im == 0.0, power) {
+            (true, 0) => c.re.to_string(),
+            (true, _) if c.re == 1.0 => String::new(),
+            (true, _) if c.re == -1.0 => "-".to_string(),
+            (true, _) => c.re.to_string(),
+            (false, _) => format!("({c})"),
+        };
+        match power {
+            0 => coefficient,
+            1 => format!("{coefficient}z"),
+            _ => format!("{coefficient}z^{power}"),
+        }
+    }).collect::<Vec<_>>();
+    if terms.is_empty() { "0".to_string() } else { terms.join(" + ") }
+}
+
+// Points in the main cardioid and the period 2 bulb, which have closed forms. Must match known_period() in shader.wgsl.
+pub fn known_period(c: Vec2<f64>) -> u32 {
+    let x = c.0 - 0.25;
+    let q = x * x + c.1 * c.1;
+    if q * (q + x) <= 0.25 * c.1 * c.1 { return 1 }
+    if (c.0 + 1.0) * (c.0 + 1.0) + c.1 * c.1 <= 0.0625 { return 2 }
+    0
+}
+
+// Brent's cycle detection: every iterate gets compared to a saved one, which gets replaced each time the number of steps
+// since it reaches the next power of two. Must match Periodicity in shader.wgsl.
+struct Periodicity {
+    saved: Vec2<f64>,
+    steps: u32,
+    window: u32,
+    epsilon: f64,
+}
+
+impl Periodicity {
+    fn new(z: Vec2<f64>, epsilon: f64) -> Self {
+        Self { saved: z, steps: 0, window: 1, epsilon }
+    }
+    
+    // The period once the orbit comes back within epsilon of the saved iterate
+    fn check(&mut self, z: Vec2<f64>) -> Option<u32> {
+        self.steps += 1;
+        if (z.0 - self.saved.0).hypot(z.1 - self.saved.1) < self.epsilon { return Some(self.steps) }
+        if self.steps == self.window {
+            self.saved = z;
+            self.steps = 0;
+            self.window *= 2;
+        }
+        None
+    }
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sample {
+    pub iterations: u32,
+    // What the palette gets indexed with, or the exponent for lyapunov fractals
+    pub value: f32,
+    // Estimated distance to the set in pixels
+    pub distance: f32,
+    // The period of the cycle an interior point's orbit settled into, or 0 if none was found
+    pub period: u32,
+    // Whether it gets colored, which is whether it escaped or converged
+    pub escaped: bool,
+    // Magnets and novas can converge instead of escaping, which gets the other half of the palette
+    pub converged: bool,
+    // The closest the orbit came to the orbit trap, infinite if there isn't one
+    pub trap: f32,
+    // Where the orbit first landed in an image trap's image, if it ever did
+    pub trap_image: Option<Vec2<f64>>,
+    // Where the orbit was last, and which iteration got closest to 0, for interior coloring
+    pub z: Vec2<f64>,
+    pub atom: u32,
+}
+
+impl Sample {
+    fn interior(period: u32) -> Self {
+        Self { iterations: 0, value: 0.0, distance: 0.0, period, escaped: false, converged: false, trap: f32::INFINITY, trap_image: None, z: Vec2(0.0, 0.0), atom: 0 }
+    }
+}
+
+// Must match palette() in shader.wgsl
+pub fn palette(t: f32) -> [f32; 3] {
+    let phase = [0.0, 0.1, 0.2];
+    phase.map(|p| 0.5 + 0.5 * (std::f32::consts::TAU * (t * 0.02 + p)).cos())
+}
+
+// Stable orbits in gold and chaotic ones in blue, both fading to black where the exponent crosses 0. Must match
+// lyapunov_color() in shader.wgsl.
+pub fn lyapunov_color(exponent: f32) -> [f32; 3] {
+    let (color, brightness) = if exponent < 0.0 { ([1.0, 0.8, 0.1], 1.0 - exponent.exp()) } else { ([0.1, 0.3, 1.0], 1.0 - (-exponent).exp()) };
+    color.map(|v| v * brightness)
+}
+
+// Must match interior_color() in shader.wgsl
+fn interior_color(params: &FractalParams, sample: &Sample) -> [f32; 3] {
+    match params.interior {
+        InteriorMode::Magnitude => palette(25.0 * sample.z.0.hypot(sample.z.1) as f32),
+        InteriorMode::Angle => palette(50.0 * (sample.z.1.atan2(sample.z.0) / std::f64::consts::TAU + 0.5) as f32),
+        // The rest need a cycle or a record to go by, and stay black without one
+        InteriorMode::Period if sample.period != 0 => palette(5.0 * sample.period as f32),
+        InteriorMode::Distance if sample.period != 0 => [(sample.distance as f64 / params.line_thickness).clamp(0.0, 1.0) as f32; 3],
+        InteriorMode::AtomDomain if sample.atom != 0 => palette(5.0 * sample.atom as f32),
+        _ => [0.0; 3],
+    }
+}
+
+// Must match shade() in shader.wgsl
+pub fn color(params: &FractalParams, sample: &Sample) -> [u8; 4] {
+    // Lyapunov fractals have no interior to speak of, just orbits that ran off to infinity
+    if !sample.escaped && params.fractal == FractalType::Lyapunov { return [0, 0, 0, 255] }
+    if !sample.escaped {
+        let [r, g, b] = interior_color(params, sample).map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8);
+        return [r, g, b, 255]
+    }
+    if params.fractal == FractalType::Lyapunov {
+        let [r, g, b] = lyapunov_color(sample.value).map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8);
+        return [r, g, b, 255]
+    }
+    if params.fractal == FractalType::Newton {
+        // Colored by the root it got to, darker the longer that took
+        let brightness = 0.93f32.powi(sample.iterations as i32);
+        let [r, g, b] = palette(sample.value).map(|v| (v.clamp(0.0, 1.0) * brightness * 255.0).round() as u8);
+        return [r, g, b, 255]
+    }
+    if params.color_mode == ColorMode::Distance && params.has_distance_estimate() {
+        let brightness = ((sample.distance as f64 / params.line_thickness).clamp(0.0, 1.0) * 255.0).round() as u8;
+        return [brightness, brightness, brightness, 255]
+    }
+    // The trap only changes how bright it is, so the bands or smooth gradient still show through. Image traps need the
+    // image, which only the gpu has, so they come out as if there was no trap here.
+    let brightness = params.trap.brightness(sample.trap);
+    let [r, g, b] = palette(sample.value).map(|v| (v.clamp(0.0, 1.0) * brightness * 255.0).round() as u8);
+    [r, g, b, 255]
+}
+
+
+pub struct FractalImage {
+    pub width: u32,
+    pub height: u32,
+    pub samples: Vec<Sample>,
+    pub rgba: Vec<u8>,
+}
+
+pub fn render_cpu(params: &FractalParams, width: u32, height: u32) -> FractalImage {
+    let mut samples = vec![Sample::interior(0); width as usize * height as usize];
+    let center = params.center_f64();
+    let pixel_size = params.pixel_size(height).to_f64();
+    
+    for_each_row(&mut samples, width as usize, |y, row| {
+        for (x, sample) in row.iter_mut().enumerate() {
+            let offset = params.pixel_offset(x as f64 + 0.5, y as f64 + 0.5, width, height);
+            *sample = params.sample(Vec2(center.0 + offset.0.to_f64(), center.1 + offset.1.to_f64()), pixel_size);
+        }
+    });
+    
+    let rgba = samples.iter().flat_map(|sample| color(params, sample)).collect();
+    FractalImage { width, height, samples, rgba }
+}
+
+// Splits rows between however many threads the machine has. Wasm has no threads so this just runs inline there.
+pub fn for_each_row<T: Send>(data: &mut [T], width: usize, f: impl Fn(usize, &mut [T]) + Sync) {
+    if width == 0 { return }
+    let rows = data.len() / width;
+    
+    #[cfg(not(target_arch = "wasm32"))]
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get()).min(rows.max(1));
+    #[cfg(target_arch = "wasm32")]
+    let threads = 1;
+    
+    if threads <= 1 {
+        for (y, row) in data.chunks_mut(width).enumerate() { f(y, row) }
+        return
+    }
+    
+    let rows_per_thread = rows.div_ceil(threads);
+    std::thread::scope(|scope| {
+        for (i, chunk) in data.chunks_mut(rows_per_thread * width).enumerate() {
+            let f = &f;
+            scope.spawn(move || {
+                for (j, row) in chunk.chunks_mut(width).enumerate() { f(i * rows_per_thread + j, row) }
+            });
+        }
+    });
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // Walks outward along the real axis from the cardioid's cusp, through a few dozen bands
+    fn line(params: &FractalParams) -> Vec<Sample> {
+        (0..2000).map(|i| params.sample(Vec2(0.26 + i as f64 * 1e-4, 0.0), 1e-4)).collect()
+    }
+    
+    #[test]
+    fn cpu_engine_matches_known_points() {
+        let params = FractalParams::default();
+        // The cardioid's center and the period 2 bulb's, which never escape
+        for point in [Vec2(0.0, 0.0), Vec2(-1.0, 0.0)] {
+            assert!(!params.sample(point, 1e-3).escaped);
+            // Without the interior shortcuts they run all the way to the limit
+            let full = FractalParams { interior: InteriorMode::Magnitude, ..params.clone() };
+            let sample = full.sample(point, 1e-3);
+            assert_eq!((sample.iterations, sample.escaped), (params.max_iterations, false));
+        }
+        // 0, 1, 2, 5 and 0, 2, 6
+        assert_eq!(params.sample(Vec2(1.0, 0.0), 1e-3).iterations, 3);
+        assert_eq!(params.sample(Vec2(2.0, 0.0), 1e-3).iterations, 2);
+        assert!(params.sample(Vec2(1.0, 0.0), 1e-3).escaped);
+        
+        // The julia set for c = 0 is the unit circle, and c = -1 has 0 on a period 2 cycle
+        let circle = FractalParams { julia: Some(Vec2(0.0, 0.0)), ..params.clone() };
+        assert!(!circle.sample(Vec2(0.5, 0.5), 1e-3).escaped);
+        assert_eq!(circle.sample(Vec2(1.5, 0.0), 1e-3).iterations, 1);
+        let basilica = FractalParams { julia: Some(Vec2(-1.0, 0.0)), ..params.clone() };
+        assert!(!basilica.sample(Vec2(0.0, 0.0), 1e-3).escaped);
+        assert!(basilica.sample(Vec2(0.0, 1.0), 1e-3).escaped);
+    }
+    
+    #[test]
+    fn locations_read_back_at_the_same_precision() {
+        let mut params = FractalParams::default();
+        params.set_location("-1.74995768370609350360221450607069970727110579726252077930242837820286008082972804887218672784431700831100544507655659531379747541999999995 0.00000000000000000000000000000000000000000001 1e-60").unwrap();
+        let limbs = BigFloat::limbs_for_scale(params.scale);
+        assert_eq!(params.center.0.fraction_limbs(), limbs);
+        
+        let first = params.clone();
+        params.set_location(&first.location()).unwrap();
+        assert_eq!(params.center.0.fraction_limbs(), limbs);
+        assert_eq!(params.center.1.fraction_limbs(), limbs);
+        assert_eq!(params.center, first.center);
+        assert_eq!(params.scale, first.scale);
+    }
+    
+    #[test]
+    fn cpu_render_covers_the_view() {
+        let params = FractalParams::default();
+        let image = render_cpu(&params, 8, 6);
+        assert_eq!((image.width, image.height), (8, 6));
+        assert_eq!(image.samples.len(), 8 * 6);
+        assert_eq!(image.rgba.len(), 8 * 6 * 4);
+        // Rows go from the top, so the top left corner is well outside the set and the middle is inside the cardioid
+        assert!(image.samples[0].escaped);
+        assert!(!image.samples[3 * 8 + 4].escaped);
+    }
+    
+    #[test]
+    fn smooth_coloring_blends_bands() {
+        let banded = FractalParams { max_iterations: 1000, bailout: 256.0, ..Default::default() };
+        let smooth = FractalParams { color_mode: ColorMode::Smooth, ..banded.clone() };
+        let (banded, smooth) = (line(&banded), line(&smooth));
+        
+        // |z| ends up between the bailout and about its square, so the fraction can only pull the value down so far
+        let offset = 1.0 - 256f32.ln().log2();
+        for (banded, smooth) in banded.iter().zip(&smooth).filter(|(sample, _)| sample.escaped) {
+            assert_eq!(banded.iterations, smooth.iterations);
+            let fraction = smooth.value - banded.value - offset;
+            assert!((-1.01..=0.0).contains(&fraction), "{} is too far from {}", smooth.value, banded.value);
+        }
+        // Banded jumps by a whole iteration at each band, smooth shouldn't jump anywhere
+        assert!(banded.windows(2).any(|pair| pair[0].value - pair[1].value >= 1.0));
+        for pair in smooth.windows(2).filter(|pair| pair[0].escaped && pair[1].escaped && pair[1].iterations < 100) {
+            assert!((pair[0].value - pair[1].value).abs() < 0.5, "{} to {}", pair[0].value, pair[1].value);
+        }
+    }
+    
+    #[test]
+    fn distance_estimate_brackets_true_distance() {
+        let params = FractalParams { max_iterations: 10000, bailout: 1e6, color_mode: ColorMode::Distance, ..Default::default() };
+        // The whole set fits in |c| <= 2, so from past the tip at -2 the nearest bit is the tip itself. With a pixel size of
+        // 1 the distance comes out in plain units.
+        for (point, distance) in [(-2.001, 0.001), (-2.01, 0.01), (-2.1, 0.1), (-2.5, 0.5)] {
+            let sample = params.sample(Vec2(point, 0.0), 1.0);
+            assert!(sample.escaped);
+            // Koebe's theorem only pins the true distance down to within a factor of 2 either way of the estimate
+            let ratio = sample.distance as f64 / distance;
+            assert!((0.5..=2.0).contains(&ratio), "estimated {} at {point}, actual {distance}", sample.distance);
+        }
+        
+        // Julia sets too, for c = 0 where the set is the unit disk
+        let julia = FractalParams { julia: Some(Vec2(0.0, 0.0)), ..params };
+        for radius in [1.01, 1.5, 3.0] {
+            let ratio = julia.sample(Vec2(0.0, radius), 1.0).distance as f64 / (radius - 1.0);
+            assert!((0.5..=2.0).contains(&ratio), "estimated ratio {ratio} at radius {radius}");
+        }
+    }
+    
+    #[test]
+    fn multibrot_matches_mandelbrot_and_its_symmetry() {
+        let mandelbrot = FractalParams { max_iterations: 500, color_mode: ColorMode::Smooth, ..Default::default() };
+        let multibrot = |exponent| FractalParams { fractal: FractalType::Multibrot, exponent, ..mandelbrot.clone() };
+        let points = (0..400).map(|i| Vec2(-2.0 + (i % 20) as f64 * 0.2, -2.0 + (i / 20) as f64 * 0.2)).collect::<Vec<_>>();
+        
+        // d = 2 is the same iteration, just without the interior shortcuts
+        for &point in &points {
+            let (a, b) = (mandelbrot.sample(point, 1e-3), multibrot(2.0).sample(point, 1e-3));
+            assert_eq!(a.escaped, b.escaped, "at {point:?}");
+            if a.escaped {
+                assert_eq!(a.iterations, b.iterations, "at {point:?}");
+                assert!((a.value - b.value).abs() < 1e-4, "{} vs {} at {point:?}", a.value, b.value);
+            }
+        }
+        
+        // z^d + c is symmetric under rotation by 2π/(d - 1)
+        let rotation = Vec2((std::f64::consts::TAU / 3.0).cos(), (std::f64::consts::TAU / 3.0).sin());
+        for &point in &points {
+            let rotated = complex_mul(point, rotation);
+            let (a, b) = (multibrot(4.0).sample(point, 1e-3), multibrot(4.0).sample(rotated, 1e-3));
+            assert!(a.iterations.abs_diff(b.iterations) <= 1, "{} vs {} at {point:?}", a.iterations, b.iterations);
+        }
+        
+        // The polar form has to agree with plain multiplication wherever both make sense
+        for &z in points.iter().filter(|z| **z != Vec2(0.0, 0.0)) {
+            let close = |a: Vec2<f64>, b: Vec2<f64>| (a.0 - b.0).hypot(a.1 - b.1) < 1e-12 * a.0.hypot(a.1).max(1.0);
+            let root = complex_pow(z, 0.5);
+            assert!(close(complex_mul(root, root), z), "sqrt {z:?}");
+            assert!(close(complex_mul(complex_pow(z, -2.0), complex_pow(z, 2.0)), Vec2(1.0, 0.0)), "inverse {z:?}");
+            assert!(close(complex_pow(z, 2.5), complex_mul(complex_pow(z, 2.0), root)), "2.5 {z:?}");
+        }
+        
+        // Negative powers: c = -1 goes 0, -1, 0, -1, ... since 0^d counts as 0
+        let negative = multibrot(-2.0);
+        assert_eq!(negative.sample(Vec2(3.0, 0.0), 1e-3).iterations, 1);
+        let cycle = negative.sample(Vec2(-1.0, 0.0), 1e-3);
+        assert!(!cycle.escaped);
+        assert_eq!(cycle.period, 2);
+        // Big z gets knocked back down rather than growing, so there's no rate to smooth by and it stays banded
+        for point in points.iter().copied().chain([Vec2(3.0, 0.0)]) {
+            let sample = negative.sample(point, 1e-3);
+            if sample.escaped { assert_eq!(sample.value, sample.iterations as f32, "at {point:?}") }
+        }
+    }
+    
+    const VARIANTS: [FractalType; 5] = [FractalType::BurningShip, FractalType::Tricorn, FractalType::Celtic, FractalType::Buffalo, FractalType::Perpendicular];
+    
+    #[test]
+    fn variants_take_the_right_first_step() {
+        // z = -1 + 2i squares to -3 - 4i
+        let (z, c) = (Vec2(-1.0, 2.0), Vec2(0.5, 0.25));
+        for (fractal, expected) in VARIANTS.into_iter().zip([(-3.0, 4.0), (-3.0, 4.0), (3.0, -4.0), (3.0, -4.0), (-3.0, -4.0)]) {
+            let params = FractalParams { fractal, ..Default::default() };
+            let (next, _) = params.step(z, Vec2(0.0, 0.0), c, 0.0);
+            assert_eq!(next, Vec2(expected.0 + c.0, expected.1 + c.1), "{fractal:?}");
+        }
+    }
+    
+    #[test]
+    fn variant_derivatives_match_finite_differences() {
+        for fractal in VARIANTS {
+            let params = FractalParams { fractal, ..Default::default() };
+            let orbit = |c: Vec2<f64>| (0..4).fold((Vec2(0.0, 0.0), Vec2(0.0, 0.0)), |(z, w), _| params.step(z, w, c, 1.0));
+            let c = Vec2(-0.3, 0.45);
+            let (z, derivative) = orbit(c);
+            let h = 1e-7;
+            let (shifted, _) = orbit(Vec2(c.0 + h, c.1));
+            let difference = Vec2((shifted.0 - z.0) / h, (shifted.1 - z.1) / h);
+            assert!((difference.0 - derivative.0).hypot(difference.1 - derivative.1) < 1e-4 * derivative.0.hypot(derivative.1), "{fractal:?}: {derivative:?} vs {difference:?}");
+        }
+    }
+    
+    // Iteration counts over a coarse grid, pinned so any change to a variant's formula shows up
+    fn golden(params: &FractalParams) -> (u64, usize) {
+        let image = render_cpu(params, 32, 24);
+        (image.samples.iter().map(|sample| sample.iterations as u64).sum(), image.samples.iter().filter(|sample| sample.escaped).count())
+    }
+    
+    #[test]
+    fn variants_match_golden_values() {
+        let expected = [((6109, 666), (892, 588)), ((3277, 722), (888, 574)), ((5479, 677), (764, 524)), ((8116, 630), (740, 512)), ((5577, 659), (880, 574))];
+        for (fractal, expected) in VARIANTS.into_iter().zip(expected) {
+            let mandelbrot = FractalParams { fractal, center: Vec2(BigFloat::from_f64(-0.4, 2), BigFloat::from_f64(-0.3, 2)), scale: FloatExp::from_f64(1.6), max_iterations: 64, ..Default::default() };
+            let julia = FractalParams { julia: Some(Vec2(-0.2, 0.05)), center: Vec2(BigFloat::zero(2), BigFloat::zero(2)), scale: FloatExp::from_f64(1.5), ..mandelbrot.clone() };
+            assert_eq!((golden(&mandelbrot), golden(&julia)), expected, "{fractal:?}");
+        }
+    }
+    
+    #[test]
+    fn convergent_families_settle_or_escape() {
+        // Magnet orbits that settle on the fixed point at 1 count as converged rather than trapped
+        let magnet = FractalParams { fractal: FractalType::MagnetI, ..Default::default() };
+        let sample = magnet.sample(Vec2(5.0, 5.0), 1e-3);
+        assert!(sample.escaped && sample.converged);
+        assert!(magnet.sample(Vec2(0.3, 0.2), 1e-3).period > 0);
+        assert!(!FractalParams { fractal: FractalType::MagnetII, ..magnet.clone() }.sample(Vec2(1.0, 0.0), 1e-3).escaped);
+        
+        // Nova starts the mandelbrot at a root of the polynomial, so c = 0 is already there
+        let nova = FractalParams { fractal: FractalType::Nova, ..Default::default() };
+        assert_eq!(nova.sample(Vec2(0.0, 0.0), 1e-3).iterations, 1);
+        assert!(!nova.sample(Vec2(-0.5, 0.0), 1e-3).escaped);
+        
+        let expected = [((3262, 718), (5610, 738)), ((6028, 702), (14087, 734)), ((2934, 708), (4891, 768)), ((20316, 760), (19028, 768))];
+        for (fractal, expected) in [FractalType::Phoenix, FractalType::MagnetI, FractalType::MagnetII, FractalType::Nova].into_iter().zip(expected) {
+            let mandelbrot = FractalParams { fractal, center: Vec2(BigFloat::from_f64(0.5, 2), BigFloat::zero(2)), scale: FloatExp::from_f64(3.0), max_iterations: 64, ..Default::default() };
+            let c = if fractal == FractalType::Phoenix { Vec2(0.5667, -0.5) } else { Vec2(1.5, 0.3) };
+            let julia = FractalParams { julia: Some(c), center: Vec2(BigFloat::zero(2), BigFloat::zero(2)), scale: FloatExp::from_f64(1.5), ..mandelbrot.clone() };
+            assert_eq!((golden(&mandelbrot), golden(&julia)), expected, "{fractal:?}");
+        }
+    }
+    
+    #[test]
+    fn newton_basins_find_the_nearest_root() {
+        let mut params = FractalParams { fractal: FractalType::Newton, ..Default::default() };
+        params.set_polynomial("roots 1 i -1 -i 2+2i").unwrap();
+        assert_eq!(params.coefficients.len(), 6);
+        assert_eq!(params.name(), "Newton p(z) = z^5 + (-2-2i)z^4 + -z + (2+2i)");
+        
+        // Right next to a root, Newton's method has nowhere else to go
+        for (k, root) in params.roots.clone().into_iter().enumerate() {
+            let sample = params.sample(Vec2(root.re + 0.01, root.im - 0.02), 1e-3);
+            assert!(sample.escaped);
+            assert_eq!(sample.value, 50.0 * k as f32 / 5.0);
+            assert!(sample.iterations < 5);
+        }
+        
+        // Under-relaxed steps converge to the same place, just slower
+        let relaxed = FractalParams { relaxation: Complex::new(0.5, 0.0), ..params.clone() };
+        let (fast, slow) = (params.sample(Vec2(0.9, 0.2), 1e-3), relaxed.sample(Vec2(0.9, 0.2), 1e-3));
+        assert_eq!(fast.value, slow.value);
+        assert!(slow.iterations > fast.iterations);
+        
+        // Coefficients get solved for their roots instead
+        params.set_polynomial("1, 0, 0, 0, -16").unwrap();
+        assert_eq!(params.roots.len(), 4);
+        assert!(params.roots.iter().all(|root| (root.abs() - 2.0).abs() < 1e-12));
+        assert!(params.set_polynomial("0 0 3").is_err());
+        assert!(params.set_polynomial("1 2 x").is_err());
+        assert!(params.set_polynomial(&["1"; 10].join(" ")).is_err());
+        assert!(params.set_polynomial(&format!("roots {}", ["1"; 9].join(" "))).is_err());
+        // Leading zeros don't count towards the degree
+        params.set_polynomial(&format!("{} 1 0 -1", ["0"; 20].join(" "))).unwrap();
+        assert_eq!(params.roots.len(), 2);
+    }
+    
+    #[test]
+    fn lyapunov_exponents_match_the_logistic_map() {
+        let mut params = FractalParams { max_iterations: 4000, ..Default::default() };
+        params.set_fractal(FractalType::Lyapunov);
+        assert_eq!(params.center_f64(), Vec2(3.0, 3.0));
+        
+        // With a = b it's the plain logistic map. At r = 3.2 there's a 2-cycle with multiplier 4 + 2r - r^2.
+        let exponent = params.lyapunov_exponent(3.2, 3.2).unwrap();
+        assert!((exponent - (4.0 + 6.4 - 3.2 * 3.2f64).ln() / 2.0).abs() < 1e-6, "{exponent}");
+        // Fully chaotic at 3.9, where the exponent is known to be about 0.49
+        let exponent = params.lyapunov_exponent(3.9, 3.9).unwrap();
+        assert!((exponent - 0.49).abs() < 0.05, "{exponent}");
+        // Past 4 the orbit gets thrown out of [0, 1]
+        assert_eq!(params.lyapunov_exponent(4.5, 3.0), None);
+        assert!(!params.sample(Vec2(-1.0, 3.0), 1e-3).escaped);
+        
+        // Swapping the letters swaps the axes
+        params.set_sequence("aabab").unwrap();
+        assert_eq!((params.sequence.as_str(), params.sequence_bits()), ("AABAB", 0b10100));
+        let swapped = FractalParams { sequence: "BBABA".to_string(), ..params.clone() };
+        assert_eq!(params.lyapunov_exponent(3.4, 2.8), swapped.lyapunov_exponent(2.8, 3.4));
+        assert!(params.set_sequence("ABC").is_err());
+        assert!(params.set_sequence("").is_err());
+        assert!(params.set_sequence(&"AB".repeat(17)).is_err());
+        
+        // Gold for stable, blue for chaotic
+        let [stable, chaotic] = [Vec2(3.2, 3.2), Vec2(3.9, 3.9)].map(|point| color(&params, &params.sample(point, 1e-3)));
+        assert!(stable[0] > stable[2] && chaotic[2] > chaotic[0], "{stable:?} {chaotic:?}");
+        
+        params.set_fractal(FractalType::Mandelbrot);
+        assert_eq!(params.center_f64(), Vec2(-0.5, 0.0));
+    }
+    
+    #[test]
+    fn interior_points_report_their_period() {
+        let params = FractalParams { max_iterations: 100000, ..Default::default() };
+        // Centers of hyperbolic components, plus some points off center
+        for (point, period) in [
+            (Vec2(0.0, 0.0), 1),
+            (Vec2(0.2, 0.3), 1),
+            (Vec2(-1.0, 0.0), 2),
+            (Vec2(-1.1, 0.1), 2),
+            (Vec2(-0.1225611668766536, 0.7448617666197442), 3),
+            (Vec2(-1.7548776662466927, 0.0), 3),
+            (Vec2(-1.3107026413368328, 0.0), 4),
+            (Vec2(0.3795135880159236, 0.3349323055974974), 5),
+        ] {
+            let sample = params.sample(point, 1e-3);
+            assert!(!sample.escaped);
+            assert_eq!(sample.period, period, "at {point:?}");
+        }
+        assert_eq!(params.sample(Vec2(0.3, 0.0), 1e-3).period, 0);
+        
+        // Julia sets skip the closed forms but still catch cycles, here the fixed point at 0
+        let julia = FractalParams { julia: Some(Vec2(0.0, 0.0)), ..params };
+        assert_eq!(julia.sample(Vec2(0.5, 0.0), 1e-3).period, 1);
+    }
+    
+    #[test]
+    fn orbit_traps_track_the_closest_approach() {
+        // 0.5 escapes after 0.5, 0.75, 1.0625, 1.62890625, 3.15...
+        let c = Vec2(0.5, 0.0);
+        let trapped = |shape, center, angle| {
+            let params = FractalParams { trap: OrbitTrap { shape: Some(shape), center, angle, ..Default::default() }, ..Default::default() };
+            params.sample(c, 1e-3).trap
+        };
+        assert!((trapped(TrapShape::Point, Vec2(1.0, 0.0), 0.0) - 0.0625).abs() < 1e-6);
+        assert!((trapped(TrapShape::Point, Vec2(0.0, 0.0), 0.0) - 0.5).abs() < 1e-6);
+        assert!((trapped(TrapShape::Circle, Vec2(0.0, 0.0), 0.0) - 0.0625).abs() < 1e-6);
+        assert_eq!(trapped(TrapShape::Line, Vec2(0.0, 0.0), 0.0), 0.0);
+        // Turned upright the line only passes closest to 0.5
+        assert!((trapped(TrapShape::Line, Vec2(0.0, 0.0), 90.0) - 0.5).abs() < 1e-6);
+        assert!(trapped(TrapShape::Cross, Vec2(0.75, 0.5), 0.0).abs() < 1e-6);
+        
+        // Traps only dim the smooth coloring, they don't change where it is in the palette
+        let smooth = FractalParams { color_mode: ColorMode::Smooth, bailout: 100.0, ..Default::default() };
+        let trap = OrbitTrap { shape: Some(TrapShape::Point), center: Vec2(1.0, 0.0), width: 0.5, ..Default::default() };
+        let trapped = FractalParams { trap, ..smooth.clone() };
+        let (plain, lit) = (smooth.sample(c, 1e-3), trapped.sample(c, 1e-3));
+        assert_eq!(plain.value, lit.value);
+        assert_eq!(plain.trap, f32::INFINITY);
+        let brightness = (-0.0625f32 / 0.5).exp();
+        let (plain, lit) = (color(&smooth, &plain), color(&trapped, &lit));
+        for k in 0..3 { assert!((lit[k] as f32 - plain[k] as f32 * brightness).abs() <= 1.0) }
+        
+        let mut trap = OrbitTrap::default();
+        trap.set("0.5 -0.25 2").unwrap();
+        assert_eq!((trap.center, trap.radius, trap.angle), (Vec2(0.5, -0.25), 2.0, 0.0));
+        assert!(trap.set("1").is_err() && trap.set("0 0 1 0 0").is_err());
+    }
+    
+    #[test]
+    fn image_traps_take_the_first_point_inside() {
+        // Same orbit as above, 0.5, 0.75, 1.0625, 1.62890625, 3.15...
+        let c = Vec2(0.5, 0.0);
+        let trapped = |center, aspect| {
+            let params = FractalParams { trap: OrbitTrap { shape: Some(TrapShape::Image), center, radius: 0.5, aspect, ..Default::default() }, ..Default::default() };
+            params.sample(c, 1e-3)
+        };
+        let close = |a: Vec2<f64>, b: Vec2<f64>| (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9;
+        // 0.6 to 1.6 wide misses 0.5 and gets 0.75 instead
+        assert!(close(trapped(Vec2(1.1, 0.0), 1.0).trap_image.unwrap(), Vec2(0.15, 0.5)));
+        assert!(close(trapped(Vec2(1.1, 0.0), 2.0).trap_image.unwrap(), Vec2(0.2, 0.5)));
+        // Up is the top of the image
+        assert!(close(trapped(Vec2(1.05, -0.25), 1.0).trap_image.unwrap(), Vec2(0.2, 0.25)));
+        assert_eq!(trapped(Vec2(0.0, 1.0), 1.0).trap_image, None);
+        assert_eq!(trapped(Vec2(1.1, 0.0), 1.0).trap, f32::INFINITY);
+    }
+    
+    #[test]
+    fn interior_modes_see_inside_the_set() {
+        // Same as exterior distance estimates, the true distance is somewhere between a quarter of the estimate and all of it
+        let distance = FractalParams { interior: InteriorMode::Distance, max_iterations: 10000, ..Default::default() };
+        for (point, period, true_distance) in [
+            (Vec2(0.0, 0.0), 1, 0.25),
+            (Vec2(-1.0, 0.0), 2, 0.25),
+            (Vec2(-1.1, 0.0), 2, 0.15),
+            (Vec2(-0.9, 0.0), 2, 0.15),
+        ] {
+            let sample = distance.sample(point, 1e-3);
+            assert!(!sample.escaped);
+            assert_eq!(sample.period, period, "at {point:?}");
+            let estimate = sample.distance as f64 * 1e-3;
+            assert!(estimate / 4.0 <= true_distance && true_distance <= estimate * 1.001, "{estimate} at {point:?}");
+        }
+        let julia = FractalParams { julia: Some(Vec2(0.0, 0.0)), ..distance.clone() };
+        assert_eq!(julia.sample(Vec2(0.5, 0.0), 1e-3).distance, 0.0);
+        
+        // The period 2 center goes 0, -1, 0, -1, ... so the record for getting close to 0 is at 2, and likewise for 3
+        let atoms = FractalParams { interior: InteriorMode::AtomDomain, ..distance.clone() };
+        assert_eq!(atoms.sample(Vec2(-1.0, 0.0), 1e-3).atom, 2);
+        assert_eq!(atoms.sample(Vec2(-0.1225611668766536, 0.7448617666197442), 1e-3).atom, 3);
+        
+        // Final |z| runs all the way to max_iterations, cycle or not
+        let magnitude = FractalParams { interior: InteriorMode::Magnitude, max_iterations: 101, ..Default::default() };
+        let sample = magnitude.sample(Vec2(-1.0, 0.0), 1e-3);
+        assert_eq!((sample.period, sample.z), (0, Vec2(-1.0, 0.0)));
+        
+        // Black stays black whatever the exterior does, the rest get colors
+        let black = FractalParams { color_mode: ColorMode::Smooth, ..Default::default() };
+        assert_eq!(color(&black, &black.sample(Vec2(-1.0, 0.0), 1e-3)), [0, 0, 0, 255]);
+        for interior in [InteriorMode::Magnitude, InteriorMode::Angle, InteriorMode::Period, InteriorMode::Distance, InteriorMode::AtomDomain] {
+            let params = FractalParams { interior, ..black.clone() };
+            assert_ne!(color(&params, &params.sample(Vec2(-1.1, 0.0), 1e-3)), [0, 0, 0, 255], "{interior:?}");
+        }
+    }
+}
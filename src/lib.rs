@@ -2,11 +2,17 @@
 
 mod common; #[allow(unused_imports)] pub use common::*;
 mod math; #[allow(unused_imports)] pub use math::*;
+mod fractal; #[allow(unused_imports)] pub use fractal::*;
+mod formula; #[allow(unused_imports)] pub use formula::*;
+mod perturbation; #[allow(unused_imports)] pub use perturbation::*;
+mod density; #[allow(unused_imports)] pub use density::*;
+mod nucleus; #[allow(unused_imports)] pub use nucleus::*;
+mod misiurewicz; #[allow(unused_imports)] pub use misiurewicz::*;
 mod teapot; #[allow(unused_imports)] pub use teapot::*;
 
 use std::sync::Arc;
 
-use winit::{application::ApplicationHandler, dpi::{PhysicalPosition, PhysicalSize}, event::{KeyEvent, WindowEvent}, event_loop::{ActiveEventLoop, EventLoop}, keyboard::{KeyCode, PhysicalKey}, window::{Window, WindowId}};
+use winit::{application::ApplicationHandler, dpi::{PhysicalPosition, PhysicalSize}, event::{KeyEvent, MouseButton, MouseScrollDelta, WindowEvent}, event_loop::{ActiveEventLoop, EventLoop}, keyboard::{KeyCode, PhysicalKey}, window::{Window, WindowId}};
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
@@ -17,6 +23,7 @@ use wasm_bindgen::prelude::*;
 pub mod canvas {
     use wasm_bindgen::UnwrapThrowExt;
     use wasm_bindgen::JsCast;
+    use wasm_bindgen::closure::Closure;
     
     const CANVAS_ID: &str = "canvas";
 
@@ -26,65 +33,621 @@ pub mod canvas {
         let canvas = document.get_element_by_id(CANVAS_ID).expect_throw("No canvas!");
         canvas.unchecked_into()
     }
+    
+    // Winit doesn't pass on files dropped onto the canvas on the web, so this listens for them itself and sends their
+    // bytes through the event loop
+    pub fn forward_dropped_files(proxy: winit::event_loop::EventLoopProxy<crate::UserEvent>) {
+        let canvas = get_canvas();
+        // Without this the browser opens the file instead of letting it drop
+        let on_drag_over = Closure::<dyn FnMut(web_sys::DragEvent)>::new(|event: web_sys::DragEvent| event.prevent_default());
+        canvas.add_event_listener_with_callback("dragover", on_drag_over.as_ref().unchecked_ref()).unwrap_throw();
+        on_drag_over.forget();
+        
+        let on_drop = Closure::<dyn FnMut(web_sys::DragEvent)>::new(move |event: web_sys::DragEvent| {
+            event.prevent_default();
+            let Some(file) = event.data_transfer().and_then(|data| data.files()).and_then(|files| files.get(0)) else { return };
+            let proxy = proxy.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                match wasm_bindgen_futures::JsFuture::from(file.array_buffer()).await {
+                    Ok(buffer) => { let _ = proxy.send_event(crate::UserEvent::TrapImage(js_sys::Uint8Array::new(&buffer).to_vec())); }
+                    Err(e) => log::error!("Couldn't read {}: {e:?}", file.name()),
+                }
+            });
+        });
+        canvas.add_event_listener_with_callback("drop", on_drop.as_ref().unchecked_ref()).unwrap_throw();
+        on_drop.forget();
+    }
 }
 
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Vertex {
-    position: [f32; 3],
-    color: [f32; 3],
+struct FractalUniforms {
+    center: [f32; 2],
+    scale: f32,
+    aspect: f32,
+    julia_c: [f32; 2],
+    max_iter: u32,
+    mode: u32,
+    center_lo: [f32; 2],
+    julia_c_lo: [f32; 2],
+    precision_mode: u32,
+    surface_width: u32,
+    use_bla: u32,
+    use_floatexp: u32,
+    scale_mantissa: f32,
+    scale_exponent: i32,
+    color_mode: u32,
+    bailout: f32,
+    viewport_height: f32,
+    line_thickness: f32,
+    fractal_type: u32,
+    exponent: f32,
+    relaxation: [f32; 2],
+    newton_degree: u32,
+    sequence: u32,
+    sequence_length: u32,
+    trap_shape: u32,
+    trap_center: [f32; 2],
+    trap_radius: f32,
+    trap_angle: f32,
+    trap_width: f32,
+    trap_aspect: f32,
+    interior_mode: u32,
+    // The array has to start on a 16 byte boundary
+    _padding: [u32; 3],
+    newton: [[f32; 4]; MAX_NEWTON_DEGREE + 1],
 }
 
-impl Vertex {
-    const ATTRIBUTES: &[wgpu::VertexAttribute] = &[
-        wgpu::VertexAttribute {
-            offset: 0,
-            shader_location: 0,
-            format: wgpu::VertexFormat::Float32x3,
-        },
-        wgpu::VertexAttribute {
-            offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-            shader_location: 1,
-            format: wgpu::VertexFormat::Float32x3,
-        },
-    ];
+const MODE_MANDELBROT: u32 = 0;
+const MODE_JULIA: u32 = 1;
+
+const PRECISION_F32: u32 = 0;
+const PRECISION_DF64: u32 = 1;
+const PRECISION_PERTURBATION: u32 = 2;
+
+const COLOR_BANDED: u32 = 0;
+const COLOR_SMOOTH: u32 = 1;
+const COLOR_DISTANCE: u32 = 2;
+
+const FRACTAL_MANDELBROT: u32 = 0;
+const FRACTAL_MULTIBROT: u32 = 1;
+const FRACTAL_BURNING_SHIP: u32 = 2;
+const FRACTAL_TRICORN: u32 = 3;
+const FRACTAL_CELTIC: u32 = 4;
+const FRACTAL_BUFFALO: u32 = 5;
+const FRACTAL_PERPENDICULAR: u32 = 6;
+const FRACTAL_NEWTON: u32 = 7;
+const FRACTAL_PHOENIX: u32 = 8;
+const FRACTAL_MAGNET_1: u32 = 9;
+const FRACTAL_MAGNET_2: u32 = 10;
+const FRACTAL_NOVA: u32 = 11;
+const FRACTAL_CUSTOM: u32 = 12;
+const FRACTAL_LYAPUNOV: u32 = 13;
+
+const TRAP_NONE: u32 = 0;
+const TRAP_POINT: u32 = 1;
+const TRAP_LINE: u32 = 2;
+const TRAP_CIRCLE: u32 = 3;
+const TRAP_CROSS: u32 = 4;
+const TRAP_IMAGE: u32 = 5;
+
+const INTERIOR_BLACK: u32 = 0;
+const INTERIOR_MAGNITUDE: u32 = 1;
+const INTERIOR_ANGLE: u32 = 2;
+const INTERIOR_PERIOD: u32 = 3;
+const INTERIOR_DISTANCE: u32 = 4;
+const INTERIOR_ATOM_DOMAIN: u32 = 5;
+
+impl FractalUniforms {
+    fn new(params: &FractalParams, viewport: Rect, precision: Precision, surface_width: u32, use_bla: bool) -> Self {
+        let center = [params.center.0.to_f64(), params.center.1.to_f64()].map(Df64::from_f64);
+        let julia_c = params.julia.map_or([Df64::ZERO; 2], |c| [c.0, c.1].map(Df64::from_f64));
+        Self {
+            center: center.map(|x| x.hi),
+            scale: params.scale.to_f64() as f32,
+            aspect: viewport.aspect(),
+            julia_c: julia_c.map(|x| x.hi),
+            max_iter: params.max_iterations,
+            mode: if params.julia.is_some() { MODE_JULIA } else { MODE_MANDELBROT },
+            center_lo: center.map(|x| x.lo),
+            julia_c_lo: julia_c.map(|x| x.lo),
+            precision_mode: match precision {
+                Precision::F32 => PRECISION_F32,
+                Precision::Df64 => PRECISION_DF64,
+                Precision::Perturbation => PRECISION_PERTURBATION,
+            },
+            surface_width,
+            use_bla: use_bla as u32,
+            use_floatexp: (precision == Precision::Perturbation && params.scale < FloatExp::from_f64(FLOATEXP_SCALE_THRESHOLD)) as u32,
+            scale_mantissa: params.scale.mantissa as f32,
+            scale_exponent: params.scale.exponent,
+            color_mode: match params.color_mode {
+                ColorMode::Banded => COLOR_BANDED,
+                ColorMode::Smooth => COLOR_SMOOTH,
+                ColorMode::Distance => COLOR_DISTANCE,
+            },
+            bailout: params.bailout as f32,
+            viewport_height: viewport.height.max(1) as f32,
+            line_thickness: params.line_thickness as f32,
+            fractal_type: match params.fractal {
+                FractalType::Mandelbrot => FRACTAL_MANDELBROT,
+                FractalType::Multibrot => FRACTAL_MULTIBROT,
+                FractalType::BurningShip => FRACTAL_BURNING_SHIP,
+                FractalType::Tricorn => FRACTAL_TRICORN,
+                FractalType::Celtic => FRACTAL_CELTIC,
+                FractalType::Buffalo => FRACTAL_BUFFALO,
+                FractalType::Perpendicular => FRACTAL_PERPENDICULAR,
+                FractalType::Newton => FRACTAL_NEWTON,
+                FractalType::Phoenix => FRACTAL_PHOENIX,
+                FractalType::MagnetI => FRACTAL_MAGNET_1,
+                FractalType::MagnetII => FRACTAL_MAGNET_2,
+                FractalType::Nova => FRACTAL_NOVA,
+                FractalType::Custom => FRACTAL_CUSTOM,
+                FractalType::Lyapunov => FRACTAL_LYAPUNOV,
+            },
+            exponent: params.exponent as f32,
+            relaxation: [params.relaxation.re as f32, params.relaxation.im as f32],
+            newton_degree: params.roots.len() as u32,
+            sequence: params.sequence_bits(),
+            sequence_length: params.sequence.len() as u32,
+            trap_shape: match params.trap.shape {
+                None => TRAP_NONE,
+                Some(TrapShape::Point) => TRAP_POINT,
+                Some(TrapShape::Line) => TRAP_LINE,
+                Some(TrapShape::Circle) => TRAP_CIRCLE,
+                Some(TrapShape::Cross) => TRAP_CROSS,
+                Some(TrapShape::Image) => TRAP_IMAGE,
+            },
+            trap_center: [params.trap.center.0 as f32, params.trap.center.1 as f32],
+            trap_radius: params.trap.radius as f32,
+            trap_angle: params.trap.angle.to_radians() as f32,
+            trap_width: params.trap.width as f32,
+            trap_aspect: params.trap.aspect as f32,
+            interior_mode: match params.interior {
+                InteriorMode::Black => INTERIOR_BLACK,
+                InteriorMode::Magnitude => INTERIOR_MAGNITUDE,
+                InteriorMode::Angle => INTERIOR_ANGLE,
+                InteriorMode::Period => INTERIOR_PERIOD,
+                InteriorMode::Distance => INTERIOR_DISTANCE,
+                InteriorMode::AtomDomain => INTERIOR_ATOM_DOMAIN,
+            },
+            _padding: [0; 3],
+            newton: std::array::from_fn(|i| {
+                let coefficient = params.coefficients.get(i).copied().unwrap_or_default();
+                let root = params.roots.get(i).copied().unwrap_or_default();
+                [coefficient.re, coefficient.im, root.re, root.im].map(|x| x as f32)
+            }),
+        }
+    }
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Precision {
+    F32,
+    Df64,
+    Perturbation,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PrecisionMode {
+    Auto,
+    Fixed(Precision),
+}
+
+// Plain f32 starts to show its pixels somewhere below this, and double-float somewhere below the next one
+const DF64_SCALE_THRESHOLD: f64 = 5e-4;
+const PERTURBATION_SCALE_THRESHOLD: f64 = 1e-10;
+// And perturbation's f32 deltas start running out of exponent below this
+const FLOATEXP_SCALE_THRESHOLD: f64 = 1e-30;
+
+impl PrecisionMode {
+    // Perturbation needs storage buffers, so without them the best it can do is double-float
+    pub fn resolve(self, scale: FloatExp, perturbation_available: bool) -> Precision {
+        let precision = match self {
+            Self::Auto if scale < FloatExp::from_f64(PERTURBATION_SCALE_THRESHOLD) => Precision::Perturbation,
+            Self::Auto if scale < FloatExp::from_f64(DF64_SCALE_THRESHOLD) => Precision::Df64,
+            Self::Auto => Precision::F32,
+            Self::Fixed(precision) => precision,
+        };
+        if precision == Precision::Perturbation && !perturbation_available { Precision::Df64 } else { precision }
+    }
     
-    fn desc() -> wgpu::VertexBufferLayout<'static> {
-        wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: Self::ATTRIBUTES,
+    pub fn next(self) -> Self {
+        match self {
+            Self::Auto => Self::Fixed(Precision::F32),
+            Self::Fixed(Precision::F32) => Self::Fixed(Precision::Df64),
+            Self::Fixed(Precision::Df64) => Self::Fixed(Precision::Perturbation),
+            Self::Fixed(Precision::Perturbation) => Self::Auto,
+        }
+    }
+}
+
+
+// Goes in front of the points in a reference orbit's storage buffer
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct OrbitHeader {
+    offset: [f32; 2],
+    offset_exponent: i32,
+    len: u32,
+}
+
+// Has to match CX_ZERO_EXPONENT in floatexp.wgsl
+const GPU_ZERO_EXPONENT: i32 = -0x3fffffff;
+
+// The shader's floatexp complex numbers share one exponent between both parts
+fn gpu_complex_exp(z: Vec2<FloatExp>) -> ([f32; 2], i32) {
+    let exponent = [z.0, z.1].iter().filter(|x| !x.is_zero()).map(|x| x.exponent).max().unwrap_or(GPU_ZERO_EXPONENT);
+    let mantissa = [z.0, z.1].map(|x| (FloatExp { exponent: x.exponent.saturating_sub(exponent), ..x }).to_f64() as f32);
+    (mantissa, exponent)
+}
+
+// Goes in front of the entries in a bla table's storage buffer
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct BlaHeader {
+    first: u32,
+    len: u32,
+    levels: u32,
+    level_starts: [u32; 32],
+    _padding: u32,
+}
+
+impl BlaHeader {
+    fn new(table: &BlaTable) -> Self {
+        let mut level_starts = [0; 32];
+        let mut start = 0;
+        for (level, entries) in table.levels.iter().enumerate() {
+            level_starts[level] = start;
+            start += entries.len() as u32;
         }
+        Self { first: table.first, len: table.levels[0].len() as u32, levels: table.levels.len() as u32, level_starts, _padding: 0 }
     }
 }
 
-type Index = u32;
+// A reference orbit and its bla table, along with the storage buffers they've been uploaded to
+struct OrbitBinding {
+    orbit: ReferenceOrbit,
+    bla: BlaTable,
+    orbit_buffer: wgpu::Buffer,
+    bla_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+// Everything perturbation needs on the gpu, none of which webgl can do
+struct Perturbation {
+    pipeline: wgpu::RenderPipeline,
+    fixup_pipeline: wgpu::RenderPipeline,
+    orbit_bind_group_layout: wgpu::BindGroupLayout,
+    glitch_bind_group_layout: wgpu::BindGroupLayout,
+    // Sized to the surface, so it gets replaced on resize
+    glitch_buffer: wgpu::Buffer,
+    glitch_bind_group: wgpu::BindGroup,
+}
+
+impl Perturbation {
+    fn create_glitch_buffer(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, width: u32, height: u32) -> (wgpu::Buffer, wgpu::BindGroup) {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Glitch flags"),
+            size: width.max(1) as u64 * height.max(1) as u64 * std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("glitch bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+        });
+        (buffer, bind_group)
+    }
+}
+
+// Must match DensityUniforms in density.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct DensityUniforms {
+    center: [f32; 2],
+    scale: f32,
+    aspect: f32,
+    width: u32,
+    height: u32,
+    seed: u32,
+    _padding: u32,
+    limits: [u32; 4],
+}
+
+// Accumulating buddhabrots on the gpu, which needs compute shaders that webgl doesn't have
+struct DensityCompute {
+    accumulate_pipeline: wgpu::ComputePipeline,
+    resolve_pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+}
+
+enum DensityCounts {
+    // Counts in a storage buffer, bound along with the image they get colored into
+    Gpu { buffer: wgpu::Buffer, bind_group: wgpu::BindGroup },
+    Cpu(DensityImage),
+}
+
+// The counts so far for the mandelbrot pane and the image they've been colored into, sized to the pane
+struct DensityTarget {
+    // The view the counts belong to. Anything about it changing starts them over.
+    view: (FractalParams, Rect, DensityMode),
+    texture: wgpu::Texture,
+    // Binds the image in place of group 0's usual texture, for fs_density() to draw
+    display_bind_group: wgpu::BindGroup,
+    counts: DensityCounts,
+    frames: u32,
+}
+
+// A copy of the glitch flags on its way back to the cpu, along with the views they were rendered for so that results
+// for a view that has since moved get thrown out
+struct GlitchReadback {
+    buffer: wgpu::Buffer,
+    // Set by map_async's callback, to whether the mapping worked
+    mapped: Arc<std::sync::OnceLock<bool>>,
+    surface_width: u32,
+    panes: Vec<(Pane, FractalParams, Rect)>,
+}
+
+// How fast zoom animations go, in halvings of the scale per second, and the least time one takes even if it barely zooms
+const ZOOM_OCTAVES_PER_SECOND: f64 = 8.0;
+const MIN_ZOOM_SECONDS: f64 = 0.5;
+
+// A flight from one view to another, like the one to a minibrot's nucleus. The scale changes at a steady ratio, and the
+// target moves towards the middle of the pane at a steady rate in screen terms, so it never goes off screen however
+// deep the zoom goes.
+struct ZoomAnimation {
+    pane: Pane,
+    fractal: FractalType,
+    start_scale: FloatExp,
+    center: Vec2<BigFloat>,
+    scale: FloatExp,
+    // From the starting center to the target one
+    offset: Vec2<FloatExp>,
+    duration: f64,
+    elapsed: f64,
+    // The view as the animation last left it, so anything else that moves it can be told apart
+    shown: (Vec2<BigFloat>, FloatExp),
+}
+
+impl ZoomAnimation {
+    fn new(params: &FractalParams, pane: Pane, center: Vec2<BigFloat>, scale: FloatExp) -> Self {
+        let offset = Vec2((&center.0 - &params.center.0).to_float_exp(), (&center.1 - &params.center.1).to_float_exp());
+        let duration = ((scale / params.scale).log2().abs() / ZOOM_OCTAVES_PER_SECOND).max(MIN_ZOOM_SECONDS);
+        Self { pane, fractal: params.fractal, start_scale: params.scale, center, scale, offset, duration, elapsed: 0.0, shown: (params.center.clone(), params.scale) }
+    }
+    
+    // Worked out backwards from the target so it stays exact however small the view gets on the way
+    fn apply(&mut self, params: &mut FractalParams) {
+        let t = (self.elapsed / self.duration).min(1.0);
+        let t = t * t * (3.0 - 2.0 * t);
+        let octaves = (self.scale / self.start_scale).log2() * t;
+        params.scale = self.start_scale * FloatExp::new(2f64.powf(octaves.fract()), octaves.trunc() as i32);
+        let remaining = (params.scale / self.start_scale) * (1.0 - t);
+        params.center = self.center.clone();
+        if t < 1.0 { params.shift_center(Vec2(-self.offset.0 * remaining, -self.offset.1 * remaining)) } else { params.scale = self.scale }
+        self.shown = (params.center.clone(), params.scale);
+    }
+    
+    // Pans, zooms, new fractals and dropped locations all replace the view out from under it
+    fn interrupted(&self, params: &FractalParams) -> bool {
+        params.fractal != self.fractal || params.center != self.shown.0 || params.scale != self.shown.1
+    }
+}
+
+// Picks where to put a secondary reference orbit: the glitched pixel closest to the middle of all the glitched pixels
+// in the rect, which lands inside the glitch as long as there's mostly just one
+fn pick_glitched_pixel(flags: &[u32], surface_width: u32, rect: Rect) -> Option<(u32, u32)> {
+    let glitched = || (rect.y..rect.y + rect.height).flat_map(move |y| (rect.x..rect.x + rect.width).map(move |x| (x, y))).filter(|&(x, y)| flags.get((y * surface_width + x) as usize).is_some_and(|&flag| flag != 0));
+    let (mut sum_x, mut sum_y, mut count) = (0.0, 0.0, 0.0);
+    for (x, y) in glitched() {
+        sum_x += x as f64;
+        sum_y += y as f64;
+        count += 1.0;
+    }
+    if count == 0.0 { return None }
+    let (mid_x, mid_y) = (sum_x / count, sum_y / count);
+    glitched().min_by(|a, b| {
+        let distance = |&(x, y): &(u32, u32)| (x as f64 - mid_x).powi(2) + (y as f64 - mid_y).powi(2);
+        distance(a).total_cmp(&distance(b))
+    })
+}
+
+
+fn create_fractal_pipeline(device: &wgpu::Device, layout: &wgpu::PipelineLayout, shader: &wgpu::ShaderModule, fragment_entry_point: &str, format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("render pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            // The full-screen triangle is generated from the vertex index, so no buffers
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some(fragment_entry_point),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache: None,
+    })
+}
+
+// Group 0's texture, which image orbit traps pick their colors from
+fn create_image_bind_group(device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout, sampler: &wgpu::Sampler, image: &image::RgbaImage) -> wgpu::BindGroup {
+    let (image_width, image_height) = image.dimensions();
+    
+    let texture_size = wgpu::Extent3d {
+        width: image_width,
+        height: image_height,
+        depth_or_array_layers: 1,
+    };
+    
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        size: texture_size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        label: Some("texture"),
+        view_formats: &[],
+    });
+    
+    queue.write_texture(
+        wgpu::TexelCopyTextureInfo {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        image,
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * image_width),
+            rows_per_image: Some(image_height),
+        },
+        texture_size,
+    );
+    
+    let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("bind group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&texture_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    })
+}
 
+// Runs f inside a validation error scope, so whatever wgpu turns away comes back here as an error instead of going to
+// the device's error handler, which panics. It's wgpu's own naga doing the checking, so nothing that passes here can
+// fail later. Neither backend this runs on waits on the gpu to report validation errors, so the scope is ready as soon
+// as it's popped and blocking on it never actually blocks.
+fn catch_validation_errors<T>(device: &wgpu::Device, f: impl FnOnce() -> T) -> Result<T> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let value = f();
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(error) => Err(format!("wgsl: {error}").into()),
+        None => Ok(value),
+    }
+}
 
-const VERTICES: &[Vertex] = &[
-    Vertex { position: [-0.1,  0.5,  0.0], color: [1.0, 0.0, 0.0] },
-    Vertex { position: [-0.5,  0.0,  0.0], color: [0.0, 1.0, 0.0] },
-    Vertex { position: [-0.3, -0.5,  0.0], color: [0.0, 0.0, 1.0] },
-    Vertex { position: [ 0.4, -0.4,  0.0], color: [1.0, 0.5, 0.0] },
-    Vertex { position: [ 0.5,  0.2,  0.0], color: [0.5, 0.0, 1.0] },
-];
+// The main shader with the formula spliced into it, so a formula that doesn't compile ends up as an error on the hud
+fn create_main_shader(device: &wgpu::Device, formula: &Formula) -> Result<wgpu::ShaderModule> {
+    let source = formula.splice(concat!(include_str!("df64.wgsl"), include_str!("shader.wgsl")));
+    catch_validation_errors(device, || device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into())
+    }))
+}
 
-const INDICES: &[Index] = &[0, 1, 2, 0, 2, 3, 0, 3, 4];
 
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Layout {
+    Single,
+    // Mandelbrot on the left, julia on the right, and the cursor over the left one picks c for the right one
+    Split,
+}
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Pane {
+    Mandelbrot = 0,
+    Julia = 1,
+}
 
+// A region of the surface in physical pixels
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
 
-struct Camera {
-    position: Vec3<f32>,
-    yaw: f32,
-    pitch: f32,
-    roll: f32,
+impl Rect {
+    pub fn contains(&self, position: PhysicalPosition<f64>) -> bool {
+        position.x >= self.x as f64 && position.x < (self.x + self.width) as f64 && position.y >= self.y as f64 && position.y < (self.y + self.height) as f64
+    }
     
+    pub fn aspect(&self) -> f32 {
+        self.width as f32 / self.height.max(1) as f32
+    }
+}
+
+// Enough digits to tell apart neighboring pixels at this scale, plus a couple spare
+fn format_complex<T: std::fmt::Display>(z: &Vec2<T>, scale: FloatExp) -> String {
+    let digits = (3.0 - scale.log10()).clamp(2.0, 17.0) as usize;
+    format!("{:.*} {:+.*}i", digits, z.0, digits, z.1)
 }
 
 
+// What's being typed into the hud for
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InputKind {
+    Polynomial,
+    Formula,
+    Sequence,
+    Trap,
+    Misiurewicz,
+}
+
+impl InputKind {
+    fn prompt(self) -> &'static str {
+        match self {
+            Self::Polynomial => "Polynomial coefficients, highest power first, or \"roots\" and the roots",
+            Self::Formula => "Formula in z and c, like z^3 + c*sin(z)",
+            Self::Sequence => "Lyapunov sequence of As and Bs, like AABAB",
+            Self::Trap => "Orbit trap center, then optionally its radius, angle in degrees and width, like 0 0 1 45 0.1",
+            Self::Misiurewicz => "Preperiod and period of the Misiurewicz point near the cursor, like 3 1",
+        }
+    }
+}
+
+pub struct TextInput {
+    kind: InputKind,
+    text: String,
+}
+
 
 pub struct State {
     window: Arc<Window>,
@@ -95,6 +658,13 @@ pub struct State {
     config: wgpu::SurfaceConfiguration,
     is_surface_configured: bool,
     render_pipeline: wgpu::RenderPipeline,
+    // Kept around for rebuilding the pipeline with a new formula
+    render_pipeline_layout: wgpu::PipelineLayout,
+    perturbation: Option<Perturbation>,
+    density_pipeline: wgpu::RenderPipeline,
+    density_compute: Option<DensityCompute>,
+    density: Option<DensityTarget>,
+    density_mode: Option<DensityMode>,
     
     font_system: glyphon::FontSystem,
     swash_cache: glyphon::SwashCache,
@@ -102,14 +672,37 @@ pub struct State {
     atlas: glyphon::TextAtlas,
     text_renderer: glyphon::TextRenderer,
     text_buffer: glyphon::Buffer,
+    pane_text_buffers: [glyphon::Buffer; 2],
     
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
     bind_group: wgpu::BindGroup,
-    uniform_buffer: wgpu::Buffer,
-    uniform_bind_group: wgpu::BindGroup,
-    camera: Camera,
+    sampler: wgpu::Sampler,
+    // One per pane, indexed by Pane
+    uniform_buffers: [wgpu::Buffer; 2],
+    uniform_bind_groups: [wgpu::BindGroup; 2],
+    orbits: [Option<OrbitBinding>; 2],
+    use_bla: bool,
+    // Re-renders whatever the main reference orbit glitched on
+    secondary_orbits: [Option<OrbitBinding>; 2],
+    // The view and rect each pane's glitches were last looked for in
+    glitch_checked: [Option<(FractalParams, Rect)>; 2],
+    glitch_readback: Option<GlitchReadback>,
+    layout: Layout,
+    precision_mode: PrecisionMode,
+    mandelbrot: FractalParams,
+    // Keeps its own camera so flipping between the two doesn't lose either view
+    julia: FractalParams,
+    julia_mode: bool,
+    picking_julia: bool,
     mouse_position: PhysicalPosition<f64>,
+    // The pane a drag started in, so it keeps panning that one even if the cursor wanders into the other
+    dragging: Option<Pane>,
+    // Takes over the keyboard while it's open
+    text_input: Option<TextInput>,
+    // Stays on the hud until the next input gets submitted, mostly for errors
+    message: Option<String>,
+    // Called off as soon as anything else moves the view
+    zoom_animation: Option<ZoomAnimation>,
     
     average_frame_dt: f32,
     previous_frame_time: std::time::Instant,
@@ -162,44 +755,8 @@ impl State {
         };
         
         
-        let image_bytes = include_bytes!("../assets/test.png");
-        let image = image::load_from_memory(image_bytes).unwrap().to_rgba8();
-        let (image_width, image_height) = image.dimensions();
-        
-        let texture_size = wgpu::Extent3d {
-            width: image_width,
-            height: image_height,
-            depth_or_array_layers: 1,
-        };
-        
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
-            size: texture_size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            label: Some("texture"),
-            view_formats: &[],
-        });
-        
-        queue.write_texture(
-            wgpu::TexelCopyTextureInfo {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &image,
-            wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * image_width),
-                rows_per_image: Some(image_height),
-            },
-            texture_size,
-        );
+        let image = image::load_from_memory(include_bytes!("../assets/test.png")).unwrap().to_rgba8();
         
-        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
@@ -232,34 +789,25 @@ impl State {
             ],
         });
         
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("bind group"),
-            layout: &bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&texture_view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&sampler),
-                },
-            ],
-        });
+        let bind_group = create_image_bind_group(&device, &queue, &bind_group_layout, &sampler, &image);
         
         
-        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        use wgpu::util::DeviceExt;
+        let mut mandelbrot = FractalParams::default();
+        mandelbrot.trap.aspect = image.width() as f64 / image.height() as f64;
+        let julia = FractalParams { center: Vec2(BigFloat::zero(2), BigFloat::zero(2)), scale: FloatExp::from_f64(1.5), julia: Some(Vec2(-0.8, 0.156)), ..mandelbrot.clone() };
+        let uniform_buffers = [&mandelbrot, &julia].map(|params| device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Uniforms"),
-            contents: bytemuck::cast_slice(&[0.0f32, 0.0, 0.0, 0.0]),
+            contents: bytemuck::cast_slice(&[FractalUniforms::new(params, Rect { x: 0, y: 0, width: size.width, height: size.height }, Precision::F32, size.width, true)]),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
+        }));
         
         let uniform_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("uniform bind group layout"),
             entries: &[
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -270,35 +818,19 @@ impl State {
             ],
         });
         
-        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        let uniform_bind_groups = [0, 1].map(|i| device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("uniform bind group"),
             layout: &uniform_bind_group_layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: uniform_buffer.as_entire_binding(),
+                    resource: uniform_buffers[i].as_entire_binding(),
                 },
             ],
-        });
+        }));
         
         
-        use wgpu::util::DeviceExt;
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex buffer"),
-            contents: bytemuck::cast_slice(teapot::VERTICES),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-        
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index buffer"),
-            contents: bytemuck::cast_slice(teapot::INDICES),
-            usage: wgpu::BufferUsages::INDEX,
-        });
-        
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into())
-        });
+        let shader = create_main_shader(&device, &mandelbrot.formula)?;
         
         let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Render pipeline layout"),
@@ -309,50 +841,109 @@ impl State {
             push_constant_ranges: &[],
         });
         
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("render pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[
-                    Vertex::desc(),
+        let render_pipeline = create_fractal_pipeline(&device, &render_pipeline_layout, &shader, "fs_main", config.format);
+        let density_pipeline = create_fractal_pipeline(&device, &render_pipeline_layout, &shader, "fs_density", config.format);
+        
+        // Without compute shaders, density images get accumulated on the cpu and uploaded instead
+        let density_available = adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS) && device.limits().max_storage_buffers_per_shader_stage >= 1 && device.limits().max_storage_textures_per_shader_stage >= 1;
+        let density_compute = density_available.then(|| {
+            let compute_entry = |binding, ty| wgpu::BindGroupLayoutEntry { binding, visibility: wgpu::ShaderStages::COMPUTE, ty, count: None };
+            let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("density bind group layout"),
+                entries: &[
+                    compute_entry(0, wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None }),
+                    compute_entry(1, wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: false }, has_dynamic_offset: false, min_binding_size: None }),
+                    compute_entry(2, wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: wgpu::TextureFormat::Rgba8Unorm,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    }),
                 ],
-                compilation_options: wgpu::PipelineCompilationOptions {
-                    constants: &[("test_constant", 0.9)],
-                    zero_initialize_workgroup_memory: false,
-                },
-            },
-            fragment: Some(wgpu::FragmentState {
+            });
+            
+            let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Density shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("density.wgsl").into())
+            });
+            
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Density pipeline layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+            let create_pipeline = |entry_point| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&layout),
                 module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions {
-                    constants: &[("test_constant", 0.9)],
-                    zero_initialize_workgroup_memory: false,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            });
+            
+            DensityCompute {
+                accumulate_pipeline: create_pipeline("cs_accumulate"),
+                resolve_pipeline: create_pipeline("cs_resolve"),
+                uniform_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Density uniforms"),
+                    size: std::mem::size_of::<DensityUniforms>() as u64,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                bind_group_layout,
+            }
+        });
+        
+        // Webgl has no storage buffers, so the perturbation shader only gets built where they exist. Writing glitch flags
+        // from the fragment shader needs a little more on top of that.
+        let perturbation_available = device.limits().max_storage_buffers_per_shader_stage >= 3 && adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::FRAGMENT_WRITABLE_STORAGE);
+        let perturbation = perturbation_available.then(|| {
+            let storage_layout_entry = |read_only| wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
                 },
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-            cache: None,
+                count: None,
+            };
+            let orbit_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("orbit bind group layout"),
+                entries: &[
+                    storage_layout_entry(true),
+                    wgpu::BindGroupLayoutEntry { binding: 1, ..storage_layout_entry(true) },
+                ],
+            });
+            let glitch_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("glitch bind group layout"),
+                entries: &[storage_layout_entry(false)],
+            });
+            
+            let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Perturbation shader"),
+                source: wgpu::ShaderSource::Wgsl(concat!(include_str!("df64.wgsl"), include_str!("shader.wgsl"), include_str!("floatexp.wgsl"), include_str!("perturbation.wgsl")).into())
+            });
+            
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Perturbation pipeline layout"),
+                bind_group_layouts: &[
+                    &bind_group_layout,
+                    &uniform_bind_group_layout,
+                    &orbit_bind_group_layout,
+                    &glitch_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+            
+            let (glitch_buffer, glitch_bind_group) = Perturbation::create_glitch_buffer(&device, &glitch_bind_group_layout, config.width, config.height);
+            Perturbation {
+                pipeline: create_fractal_pipeline(&device, &layout, &shader, "fs_perturbation", config.format),
+                fixup_pipeline: create_fractal_pipeline(&device, &layout, &shader, "fs_perturbation_fixup", config.format),
+                orbit_bind_group_layout,
+                glitch_bind_group_layout,
+                glitch_buffer,
+                glitch_bind_group,
+            }
         });
         
         
@@ -366,6 +957,11 @@ impl State {
         text_buffer.set_size(&mut font_system, Some(300.0), Some(100.0));
         text_buffer.set_text(&mut font_system, "Text text!", &glyphon::Attrs::new().color(glyphon::Color::rgb(255, 255, 255)), glyphon::Shaping::Basic);
         text_buffer.shape_until_scroll(&mut font_system, false);
+        let pane_text_buffers = [0, 1].map(|_| {
+            let mut buffer = glyphon::Buffer::new(&mut font_system, glyphon::Metrics { font_size: 16.0, line_height: 16.0 });
+            buffer.set_size(&mut font_system, Some(800.0), Some(100.0));
+            buffer
+        });
         
         
         Ok(Self {
@@ -377,6 +973,12 @@ impl State {
             config,
             is_surface_configured: false,
             render_pipeline,
+            render_pipeline_layout,
+            perturbation,
+            density_pipeline,
+            density_compute,
+            density: None,
+            density_mode: None,
             
             font_system,
             swash_cache,
@@ -384,14 +986,29 @@ impl State {
             atlas,
             text_renderer,
             text_buffer,
+            pane_text_buffers,
             
-            vertex_buffer,
-            index_buffer,
+            bind_group_layout,
             bind_group,
-            uniform_buffer,
-            uniform_bind_group,
-            camera: Camera { position: Vec3(0.0, 0.0, 0.0), yaw: 0.0, pitch: 0.0, roll: 0.0 },
+            sampler,
+            uniform_buffers,
+            uniform_bind_groups,
+            orbits: [None, None],
+            use_bla: true,
+            secondary_orbits: [None, None],
+            glitch_checked: [None, None],
+            glitch_readback: None,
+            layout: Layout::Single,
+            precision_mode: PrecisionMode::Auto,
+            mandelbrot,
+            julia,
+            julia_mode: false,
+            picking_julia: false,
             mouse_position: PhysicalPosition { x: 0.0, y: 0.0 },
+            dragging: None,
+            text_input: None,
+            message: None,
+            zoom_animation: None,
             
             average_frame_dt: 0.0,
             previous_frame_time: std::time::Instant::now(),
@@ -407,6 +1024,461 @@ impl State {
         self.is_surface_configured = true;
         
         self.viewport.update(&self.queue, glyphon::Resolution { width: self.config.width, height: self.config.height });
+        
+        if let Some(perturbation) = &mut self.perturbation {
+            (perturbation.glitch_buffer, perturbation.glitch_bind_group) = Perturbation::create_glitch_buffer(&self.device, &perturbation.glitch_bind_group_layout, self.config.width, self.config.height);
+            self.glitch_checked = [None, None];
+        }
+    }
+    
+    pub fn pane_params(&self, pane: Pane) -> &FractalParams {
+        match pane {
+            Pane::Mandelbrot => &self.mandelbrot,
+            Pane::Julia => &self.julia,
+        }
+    }
+    
+    pub fn pane_params_mut(&mut self, pane: Pane) -> &mut FractalParams {
+        match pane {
+            Pane::Mandelbrot => &mut self.mandelbrot,
+            Pane::Julia => &mut self.julia,
+        }
+    }
+    
+    // Where a pane would be drawn, whether or not it's currently visible
+    pub fn pane_rect(&self, pane: Pane) -> Rect {
+        let (width, height) = (self.config.width, self.config.height);
+        match (self.layout, pane) {
+            (Layout::Single, _) => Rect { x: 0, y: 0, width, height },
+            (Layout::Split, Pane::Mandelbrot) => Rect { x: 0, y: 0, width: width / 2, height },
+            (Layout::Split, Pane::Julia) => Rect { x: width / 2, y: 0, width: width - width / 2, height },
+        }
+    }
+    
+    pub fn visible_panes(&self) -> Vec<Pane> {
+        match (self.layout, self.julia_mode) {
+            (Layout::Single, false) => vec![Pane::Mandelbrot],
+            (Layout::Single, true) => vec![Pane::Julia],
+            (Layout::Split, _) => vec![Pane::Mandelbrot, Pane::Julia],
+        }
+    }
+    
+    pub fn pane_at(&self, position: PhysicalPosition<f64>) -> Option<Pane> {
+        self.visible_panes().into_iter().find(|&pane| self.pane_rect(pane).contains(position))
+    }
+    
+    pub fn pane_to_complex(&self, pane: Pane, position: PhysicalPosition<f64>) -> Vec2<f64> {
+        let rect = self.pane_rect(pane);
+        self.pane_params(pane).pixel_to_complex(position.x - rect.x as f64, position.y - rect.y as f64, rect.width, rect.height)
+    }
+    
+    // Relative to the pane's center, which is all that fits in an f64 at deep zoom
+    pub fn pane_offset(&self, pane: Pane, position: PhysicalPosition<f64>) -> Vec2<FloatExp> {
+        let rect = self.pane_rect(pane);
+        self.pane_params(pane).pixel_offset(position.x - rect.x as f64, position.y - rect.y as f64, rect.width, rect.height)
+    }
+    
+    // Drag distances are in physical pixels
+    pub fn pan(&mut self, pane: Pane, dx: f64, dy: f64) {
+        self.zoom_animation = None;
+        let height = self.pane_rect(pane).height as f64;
+        let params = self.pane_params_mut(pane);
+        let pixel_size = params.scale * (2.0 / height);
+        params.shift_center(Vec2(pixel_size * -dx, pixel_size * dy));
+    }
+    
+    // Zooms around whatever is under the cursor so it stays put
+    pub fn zoom(&mut self, factor: f64) {
+        let Some(pane) = self.pane_at(self.mouse_position) else { return };
+        self.zoom_animation = None;
+        let anchor = self.pane_offset(pane, self.mouse_position);
+        let params = self.pane_params_mut(pane);
+        params.scale = params.scale * factor;
+        params.shift_center(Vec2(anchor.0 * (1.0 - factor), anchor.1 * (1.0 - factor)));
+    }
+    
+    // Finds the period of the atom domain under the cursor, solves for that period's nucleus, and flies to a framing of
+    // its minibrot
+    pub fn zoom_to_minibrot(&mut self) -> Result<()> {
+        let (cursor, limbs) = self.mandelbrot_cursor()?;
+        let period = atom_period(&cursor.center, cursor.max_iterations);
+        let nucleus = find_nucleus(&cursor.center, period, limbs)?;
+        self.message = Some(format!("Period {} minibrot, size {:.3e}", nucleus.period, nucleus.size));
+        self.zoom_animation = Some(ZoomAnimation::new(&self.mandelbrot, Pane::Mandelbrot, nucleus.c, nucleus.size * MINIBROT_FRAMING));
+        Ok(())
+    }
+    
+    // The Misiurewicz prompt, filled in with whatever preperiod and period the orbit under the cursor suggests
+    fn misiurewicz_input(&self) -> TextInput {
+        let periods = self.mandelbrot_cursor().ok().and_then(|(cursor, _)| misiurewicz_periods(&cursor.center, cursor.max_iterations));
+        TextInput { kind: InputKind::Misiurewicz, text: periods.map(|(preperiod, period)| format!("{preperiod} {period}")).unwrap_or_default() }
+    }
+    
+    // Parses "preperiod period", solves for that Misiurewicz point starting from the cursor, and recenters on it exactly
+    fn center_on_misiurewicz(&mut self, text: &str) -> Result<()> {
+        let numbers = text.split_whitespace().map(str::parse::<u32>).collect::<std::result::Result<Vec<_>, _>>()?;
+        let [preperiod, period] = numbers[..] else { return Err(format!("expected \"preperiod period\", got {text:?}").into()) };
+        let (cursor, limbs) = self.mandelbrot_cursor()?;
+        let point = find_misiurewicz(&cursor.center, preperiod, period, limbs)?;
+        // Newton's method can wander off to some other point entirely, which isn't the one anybody was pointing at
+        let offset = Vec2((&point.c.0 - &self.mandelbrot.center.0).to_float_exp(), (&point.c.1 - &self.mandelbrot.center.1).to_float_exp());
+        let rect = self.pane_rect(Pane::Mandelbrot);
+        if offset.0.abs() > self.mandelbrot.scale * (rect.width as f64 / rect.height as f64) || offset.1.abs() > self.mandelbrot.scale {
+            return Err(format!("the nearest preperiod {preperiod} period {period} point is off the pane").into())
+        }
+        self.zoom_animation = None;
+        self.mandelbrot.center = point.c;
+        Ok(())
+    }
+    
+    // The mandelbrot view centered on the cursor instead, and how many limbs the view's pixels need. These solvers only
+    // know the plain quadratic mandelbrot set.
+    fn mandelbrot_cursor(&self) -> Result<(FractalParams, usize)> {
+        if self.pane_at(self.mouse_position) != Some(Pane::Mandelbrot) || self.mandelbrot.fractal != FractalType::Mandelbrot {
+            return Err("that only works with the cursor over the mandelbrot set".into())
+        }
+        let mut cursor = self.mandelbrot.clone();
+        cursor.shift_center(self.pane_offset(Pane::Mandelbrot, self.mouse_position));
+        let limbs = BigFloat::limbs_for_scale(cursor.scale).max(cursor.center.0.fraction_limbs());
+        Ok((cursor, limbs))
+    }
+    
+    fn animate_zoom(&mut self, dt: f64) {
+        let Some(mut animation) = self.zoom_animation.take() else { return };
+        if animation.interrupted(self.pane_params(animation.pane)) { return }
+        animation.elapsed += dt;
+        animation.apply(self.pane_params_mut(animation.pane));
+        if animation.elapsed < animation.duration { self.zoom_animation = Some(animation) }
+    }
+    
+    // The cursor always maps through the mandelbrot view, even while the julia set is on screen during a live pick
+    pub fn pick_julia(&mut self) {
+        self.julia.julia = Some(self.pane_to_complex(Pane::Mandelbrot, self.mouse_position));
+        if self.layout == Layout::Single { self.julia_mode = true }
+    }
+    
+    pub fn type_key(&mut self, event: &KeyEvent) {
+        let Some(input) = &mut self.text_input else { return };
+        if !event.state.is_pressed() { return }
+        match event.physical_key {
+            PhysicalKey::Code(KeyCode::Escape) => self.text_input = None,
+            PhysicalKey::Code(KeyCode::Enter | KeyCode::NumpadEnter) => if let Some(input) = self.text_input.take() {
+                self.message = self.submit_input(input).err().map(|e| format!("{e}"));
+            }
+            PhysicalKey::Code(KeyCode::Backspace) => { input.text.pop(); }
+            _ => if let Some(text) = &event.text { input.text.extend(text.chars().filter(|c| !c.is_control())) }
+        }
+    }
+    
+    fn submit_input(&mut self, input: TextInput) -> Result<()> {
+        match input.kind {
+            // Both panes show the same polynomial
+            InputKind::Polynomial => {
+                self.mandelbrot.set_polynomial(&input.text)?;
+                self.mandelbrot.set_fractal(FractalType::Newton);
+                self.julia.coefficients = self.mandelbrot.coefficients.clone();
+                self.julia.roots = self.mandelbrot.roots.clone();
+                self.julia.set_fractal(FractalType::Newton);
+            }
+            InputKind::Sequence => {
+                self.mandelbrot.set_sequence(&input.text)?;
+                self.mandelbrot.set_fractal(FractalType::Lyapunov);
+                self.julia.sequence = self.mandelbrot.sequence.clone();
+                self.julia.set_fractal(FractalType::Lyapunov);
+            }
+            InputKind::Formula => self.set_formula(&input.text)?,
+            InputKind::Trap => {
+                self.mandelbrot.trap.set(&input.text)?;
+                if self.mandelbrot.trap.shape.is_none() { self.mandelbrot.trap.shape = Some(TrapShape::Point) }
+                self.julia.trap = self.mandelbrot.trap;
+            }
+            InputKind::Misiurewicz => self.center_on_misiurewicz(&input.text)?,
+        }
+        Ok(())
+    }
+    
+    // Swaps in a png for image traps to pick colors from, and switches both panes over to one
+    pub fn set_trap_image(&mut self, bytes: &[u8]) -> Result<()> {
+        let image = image::load_from_memory_with_format(bytes, image::ImageFormat::Png)?.to_rgba8();
+        let limit = self.device.limits().max_texture_dimension_2d;
+        if image.width() > limit || image.height() > limit { return Err(format!("the image is bigger than the {limit}px the gpu can take").into()) }
+        self.bind_group = create_image_bind_group(&self.device, &self.queue, &self.bind_group_layout, &self.sampler, &image);
+        for params in [&mut self.mandelbrot, &mut self.julia] {
+            params.trap.aspect = image.width() as f64 / image.height() as f64;
+            params.trap.shape = Some(TrapShape::Image);
+        }
+        Ok(())
+    }
+    
+    // Both panes draw with the one pipeline, so they share the formula too. Nothing changes if it doesn't compile.
+    fn set_formula(&mut self, text: &str) -> Result<()> {
+        let formula = text.parse::<Formula>()?;
+        let shader = create_main_shader(&self.device, &formula)?;
+        self.render_pipeline = catch_validation_errors(&self.device, || create_fractal_pipeline(&self.device, &self.render_pipeline_layout, &shader, "fs_main", self.config.format))?;
+        for params in [&mut self.mandelbrot, &mut self.julia] {
+            params.formula = formula.clone();
+            params.set_fractal(FractalType::Custom);
+        }
+        Ok(())
+    }
+    
+    pub fn precision(&self, pane: Pane) -> Precision {
+        let params = self.pane_params(pane);
+        if !params.supports_deep_zoom() { return Precision::F32 }
+        self.precision_mode.resolve(params.scale, self.perturbation.is_some())
+    }
+    
+    // Buddhabrots are of the mandelbrot iteration in parameter space, so it's only ever that pane that draws one
+    pub fn density_mode(&self, pane: Pane) -> Option<DensityMode> {
+        if pane != Pane::Mandelbrot || self.mandelbrot.fractal != FractalType::Mandelbrot { return None }
+        self.density_mode
+    }
+    
+    fn create_density_target(&self, view: (FractalParams, Rect, DensityMode)) -> DensityTarget {
+        let rect = view.1;
+        let storage = if self.density_compute.is_some() { wgpu::TextureUsages::STORAGE_BINDING } else { wgpu::TextureUsages::empty() };
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d { width: rect.width.max(1), height: rect.height.max(1), depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | storage,
+            label: Some("Density image"),
+            view_formats: &[],
+        });
+        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let display_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("density display bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+        
+        let counts = match &self.density_compute {
+            Some(compute) => {
+                // The busiest pixel's counts go in front, padded out to 4
+                let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Density counts"),
+                    size: (4 + 3 * rect.width.max(1) as u64 * rect.height.max(1) as u64) * std::mem::size_of::<u32>() as u64,
+                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("density bind group"),
+                    layout: &compute.bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: compute.uniform_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: wgpu::BindingResource::TextureView(&texture_view),
+                        },
+                    ],
+                });
+                DensityCounts::Gpu { buffer, bind_group }
+            }
+            None => DensityCounts::Cpu(DensityImage::new(rect.width.max(1), rect.height.max(1))),
+        };
+        DensityTarget { view, texture, display_bind_group, counts, frames: 0 }
+    }
+    
+    // Adds another frame's worth of orbits to the mandelbrot pane's density image, starting it over first if the view
+    // moved. Same sized views reuse the last one's buffers.
+    fn update_density(&mut self, encoder: &mut wgpu::CommandEncoder, mode: DensityMode, rect: Rect) {
+        let view = (self.mandelbrot.clone(), rect, mode);
+        match &mut self.density {
+            Some(density) if density.view == view => {}
+            Some(density) if density.view.1 == rect => {
+                match &mut density.counts {
+                    DensityCounts::Gpu { buffer, .. } => encoder.clear_buffer(buffer, 0, None),
+                    DensityCounts::Cpu(image) => image.clear(),
+                }
+                density.view = view;
+                density.frames = 0;
+            }
+            _ => self.density = Some(self.create_density_target(view)),
+        }
+        
+        let density = self.density.as_mut().unwrap();
+        let params = &density.view.0;
+        let limits = mode.limits(params.max_iterations);
+        let seed = pcg(density.frames);
+        density.frames += 1;
+        match &mut density.counts {
+            DensityCounts::Gpu { bind_group, .. } => {
+                let compute = self.density_compute.as_ref().unwrap();
+                let center = params.center_f64();
+                let uniforms = DensityUniforms {
+                    center: [center.0 as f32, center.1 as f32],
+                    scale: params.scale.to_f64() as f32,
+                    aspect: rect.aspect(),
+                    width: rect.width,
+                    height: rect.height,
+                    seed,
+                    _padding: 0,
+                    limits: [limits[0], limits[1], limits[2], limits.into_iter().max().unwrap()],
+                };
+                self.queue.write_buffer(&compute.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+                
+                let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Density pass"), timestamp_writes: None });
+                pass.set_bind_group(0, &*bind_group, &[]);
+                pass.set_pipeline(&compute.accumulate_pipeline);
+                pass.dispatch_workgroups(DENSITY_SAMPLES_PER_FRAME / DENSITY_WORKGROUP_SAMPLES, 1, 1);
+                pass.set_pipeline(&compute.resolve_pipeline);
+                pass.dispatch_workgroups(rect.width.div_ceil(8), rect.height.div_ceil(8), 1);
+            }
+            DensityCounts::Cpu(image) => {
+                image.accumulate(params, limits, seed, CPU_DENSITY_SAMPLES_PER_FRAME);
+                self.queue.write_texture(
+                    wgpu::TexelCopyTextureInfo {
+                        texture: &density.texture,
+                        mip_level: 0,
+                        origin: wgpu::Origin3d::ZERO,
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    &image.rgba(),
+                    wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(4 * image.width),
+                        rows_per_image: Some(image.height),
+                    },
+                    density.texture.size(),
+                );
+            }
+        }
+    }
+    
+    // Uploads an orbit and a bla table for it into a slot, reusing the slot's buffers if they fit
+    fn upload_orbit(&self, slot: Option<OrbitBinding>, orbit: ReferenceOrbit, params: &FractalParams, max_dc: FloatExp) -> OrbitBinding {
+        let perturbation = self.perturbation.as_ref().expect("reference orbits are only used with perturbation");
+        let header_size = std::mem::size_of::<OrbitHeader>() as u64;
+        let orbit_size = header_size + (params.max_iterations as u64 + 1) * std::mem::size_of::<[f32; 2]>() as u64;
+        // Every level is at most half the one before it
+        let bla_size = std::mem::size_of::<BlaHeader>() as u64 + 2 * params.max_iterations.max(1) as u64 * std::mem::size_of::<GpuBla>() as u64;
+        
+        let (orbit_buffer, bla_buffer, bind_group) = match slot {
+            Some(binding) if binding.orbit_buffer.size() >= orbit_size && binding.bla_buffer.size() >= bla_size => (binding.orbit_buffer, binding.bla_buffer, binding.bind_group),
+            _ => {
+                let [orbit_buffer, bla_buffer] = [("Reference orbit", orbit_size), ("Bla table", bla_size)].map(|(label, size)| self.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(label),
+                    size,
+                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }));
+                let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("orbit bind group"),
+                    layout: &perturbation.orbit_bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: orbit_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: bla_buffer.as_entire_binding(),
+                        },
+                    ],
+                });
+                (orbit_buffer, bla_buffer, bind_group)
+            }
+        };
+        
+        let (offset, offset_exponent) = gpu_complex_exp(Vec2((&orbit.point.0 - &params.center.0).to_float_exp(), (&orbit.point.1 - &params.center.1).to_float_exp()));
+        let header = OrbitHeader { offset, offset_exponent, len: orbit.points.len() as u32 };
+        self.queue.write_buffer(&orbit_buffer, 0, bytemuck::cast_slice(&[header]));
+        self.queue.write_buffer(&orbit_buffer, header_size, bytemuck::cast_slice(&orbit.gpu_points()));
+        
+        let bla = BlaTable::compute(&orbit, max_dc);
+        self.write_bla(&bla_buffer, &bla);
+        OrbitBinding { orbit, bla, orbit_buffer, bla_buffer, bind_group }
+    }
+    
+    fn write_bla(&self, buffer: &wgpu::Buffer, bla: &BlaTable) {
+        self.queue.write_buffer(buffer, 0, bytemuck::cast_slice(&[BlaHeader::new(bla)]));
+        self.queue.write_buffer(buffer, std::mem::size_of::<BlaHeader>() as u64, bytemuck::cast_slice(&bla.gpu_entries()));
+    }
+    
+    // How far any pixel in the pane is from the orbit's point, which is the biggest dc its bla table has to work for
+    fn max_dc(&self, pane: Pane, orbit: &ReferenceOrbit) -> FloatExp {
+        let params = self.pane_params(pane);
+        let offset = Vec2((&orbit.point.0 - &params.center.0).to_float_exp(), (&orbit.point.1 - &params.center.1).to_float_exp());
+        params.scale * (self.pane_rect(pane).aspect() as f64).hypot(1.0) + offset.0.hypot(offset.1)
+    }
+    
+    // Recomputes the pane's reference orbit if the view moved. The secondary orbit's offset was relative to the old
+    // center, so that one goes too.
+    fn update_reference_orbits(&mut self, pane: Pane) {
+        let params = self.pane_params(pane).clone();
+        let index = pane as usize;
+        if !self.orbits[index].as_ref().is_some_and(|binding| binding.orbit.matches(&params.center, &params)) {
+            let orbit = ReferenceOrbit::compute(params.center.clone(), &params);
+            let max_dc = self.max_dc(pane, &orbit);
+            let slot = self.orbits[index].take();
+            let binding = self.upload_orbit(slot, orbit, &params, max_dc);
+            self.orbits[index] = Some(binding);
+            self.secondary_orbits[index] = None;
+        }
+        if self.secondary_orbits[index].as_ref().is_some_and(|binding| !binding.orbit.matches(&binding.orbit.point, &params)) {
+            self.secondary_orbits[index] = None;
+        }
+        
+        // Zooming without moving the center keeps the orbit but changes how far out its pixels go
+        for secondary in [false, true] {
+            let slots = if secondary { &self.secondary_orbits } else { &self.orbits };
+            let Some(binding) = &slots[index] else { continue };
+            let max_dc = self.max_dc(pane, &binding.orbit);
+            if binding.bla.max_dc == max_dc { continue }
+            let bla = BlaTable::compute(&binding.orbit, max_dc);
+            self.write_bla(&binding.bla_buffer, &bla);
+            let slots = if secondary { &mut self.secondary_orbits } else { &mut self.orbits };
+            slots[index].as_mut().unwrap().bla = bla;
+        }
+    }
+    
+    // Once the glitch flags make it back to the cpu, puts a secondary reference orbit in the middle of each pane's
+    // glitches. Panes whose view moved in the meantime just get checked again.
+    fn finish_glitch_readback(&mut self) {
+        let Some(readback) = &self.glitch_readback else { return };
+        let Some(&mapped) = readback.mapped.get() else { return };
+        let readback = self.glitch_readback.take().unwrap();
+        if !mapped {
+            self.glitch_checked = [None, None];
+            return
+        }
+        
+        let flags = bytemuck::cast_slice::<u8, u32>(&readback.buffer.slice(..).get_mapped_range()).to_vec();
+        readback.buffer.unmap();
+        
+        for (pane, params, rect) in readback.panes {
+            if *self.pane_params(pane) != params || self.pane_rect(pane) != rect || readback.surface_width != self.config.width {
+                self.glitch_checked[pane as usize] = None;
+                continue
+            }
+            let Some((x, y)) = pick_glitched_pixel(&flags, readback.surface_width, rect) else { continue };
+            
+            let mut shifted = params.clone();
+            shifted.shift_center(self.pane_offset(pane, PhysicalPosition { x: x as f64 + 0.5, y: y as f64 + 0.5 }));
+            let orbit = ReferenceOrbit::compute(shifted.center, &params);
+            let max_dc = self.max_dc(pane, &orbit);
+            let slot = self.secondary_orbits[pane as usize].take();
+            let binding = self.upload_orbit(slot, orbit, &params, max_dc);
+            self.secondary_orbits[pane as usize] = Some(binding);
+        }
     }
     
     pub fn update(&mut self) {
@@ -421,19 +1493,89 @@ impl State {
         let dt = now.duration_since(self.previous_frame_time).as_secs_f32();
         self.previous_frame_time = now;
         self.average_frame_dt = 0.99 * self.average_frame_dt + 0.01 * dt;
+        self.animate_zoom(dt as f64);
         
-        self.text_buffer.set_text(&mut self.font_system, &format!("Fps: {}", 1.0 / self.average_frame_dt), &glyphon::Attrs::new().color(glyphon::Color::rgb(255, 255, 255)), glyphon::Shaping::Basic);
+        let mut text = format!("Fps: {}", 1.0 / self.average_frame_dt);
+        if let Some(input) = &self.text_input { text += &format!("\n{}: {}_", input.kind.prompt(), input.text) }
+        if let Some(message) = &self.message { text += &format!("\n{message}") }
+        self.text_buffer.set_text(&mut self.font_system, &text, &glyphon::Attrs::new().color(glyphon::Color::rgb(255, 255, 255)), glyphon::Shaping::Basic);
         
+        let panes = self.visible_panes();
+        for &pane in &panes {
+            let params = self.pane_params(pane);
+            let mut text = match pane {
+                Pane::Mandelbrot => params.name(),
+                Pane::Julia if params.fractal == FractalType::Mandelbrot => format!("Julia c = {}", format_complex(&params.julia.unwrap_or(Vec2(0.0, 0.0)), self.mandelbrot.scale)),
+                Pane::Julia => format!("{} julia c = {}", params.name(), format_complex(&params.julia.unwrap_or(Vec2(0.0, 0.0)), self.mandelbrot.scale)),
+            };
+            let precision = match self.precision(pane) {
+                Precision::F32 => "f32",
+                Precision::Df64 => "df64",
+                Precision::Perturbation => "perturbation",
+            };
+            let floatexp = if precision == "perturbation" && params.scale < FloatExp::from_f64(FLOATEXP_SCALE_THRESHOLD) { " + floatexp" } else { "" };
+            let bla = if precision == "perturbation" && self.use_bla { " + bla" } else { "" };
+            text += &format!("\nCenter {}\nScale {:.3e} ({precision}{floatexp}{bla}{})", format_complex(&params.center, params.scale), params.scale, if self.precision_mode == PrecisionMode::Auto { ", auto" } else { "" });
+            // Perturbation doesn't look for cycles, so these have nothing to go on down there
+            let black = if precision == "perturbation" && matches!(params.interior, InteriorMode::Period | InteriorMode::Distance) { " (black under perturbation)" } else { "" };
+            text += &format!("\nColoring {:?}, interior {:?}{black}, bailout {}", params.color_mode, params.interior, params.bailout);
+            if params.color_mode == ColorMode::Distance || params.interior == InteriorMode::Distance { text += &format!(", lines {}px", params.line_thickness) }
+            if let Some(shape) = params.trap.shape {
+                let trap = params.trap;
+                text += &format!("\n{shape:?} trap at {} {}", trap.center.0, trap.center.1);
+                if matches!(shape, TrapShape::Circle | TrapShape::Image) { text += &format!(", radius {}", trap.radius) }
+                if matches!(shape, TrapShape::Line | TrapShape::Cross | TrapShape::Image) { text += &format!(", angle {}°", trap.angle) }
+                if shape != TrapShape::Image { text += &format!(", width {}", trap.width) }
+            }
+            if let Some(mode) = self.density_mode(pane) {
+                let frames = self.density.as_ref().map_or(0, |density| density.frames);
+                let samples_per_frame = if self.density_compute.is_some() { DENSITY_SAMPLES_PER_FRAME } else { CPU_DENSITY_SAMPLES_PER_FRAME };
+                text += &format!("\n{mode:?}, {} samples", frames as u64 * samples_per_frame as u64);
+            }
+            self.pane_text_buffers[pane as usize].set_text(&mut self.font_system, &text, &glyphon::Attrs::new().color(glyphon::Color::rgb(255, 255, 255)), glyphon::Shaping::Basic);
+        }
         
-        self.text_renderer.prepare(&self.device, &self.queue, &mut self.font_system, &mut self.atlas, &self.viewport, [glyphon::TextArea {
+        let scale_factor = self.window.scale_factor() as f32;
+        let (width, height) = (self.config.width as i32, self.config.height as i32);
+        let pane_rects = panes.iter().map(|&pane| (pane, self.pane_rect(pane))).collect::<Vec<_>>();
+        let text_areas = std::iter::once(glyphon::TextArea {
             buffer: &self.text_buffer,
             left: 10.0,
             top: 10.0,
-            scale: self.window.scale_factor() as f32,
-            bounds: glyphon::TextBounds { left: 10, top: 10, right: self.config.width as i32 - 10, bottom: self.config.height as i32 - 10, },
+            scale: scale_factor,
+            bounds: glyphon::TextBounds { left: 10, top: 10, right: width - 10, bottom: height - 10, },
             default_color: glyphon::Color::rgb(255, 255, 255),
             custom_glyphs: &[],
-        }], &mut self.swash_cache).unwrap();
+        }).chain(pane_rects.iter().map(|&(pane, rect)| {
+            glyphon::TextArea {
+                buffer: &self.pane_text_buffers[pane as usize],
+                left: rect.x as f32 + 10.0,
+                top: 30.0,
+                scale: scale_factor,
+                bounds: glyphon::TextBounds { left: rect.x as i32 + 10, top: 30, right: (rect.x + rect.width) as i32 - 10, bottom: height - 10, },
+                default_color: glyphon::Color::rgb(255, 255, 255),
+                custom_glyphs: &[],
+            }
+        }));
+        
+        self.text_renderer.prepare(&self.device, &self.queue, &mut self.font_system, &mut self.atlas, &self.viewport, text_areas, &mut self.swash_cache).unwrap();
+        
+        
+        self.finish_glitch_readback();
+        let mut glitch_checks = Vec::new();
+        for &(pane, rect) in &pane_rects {
+            let precision = self.precision(pane);
+            if precision == Precision::Perturbation && self.density_mode(pane).is_none() {
+                self.update_reference_orbits(pane);
+                let view = (self.pane_params(pane).clone(), rect);
+                if self.glitch_readback.is_none() && self.glitch_checked[pane as usize].as_ref() != Some(&view) {
+                    glitch_checks.push((pane, view.0.clone(), rect));
+                    self.glitch_checked[pane as usize] = Some(view);
+                }
+            }
+            let uniforms = FractalUniforms::new(self.pane_params(pane), rect, precision, self.config.width, self.use_bla);
+            self.queue.write_buffer(&self.uniform_buffers[pane as usize], 0, bytemuck::cast_slice(&[uniforms]));
+        }
         
         
         let output = self.surface.get_current_texture()?;
@@ -442,6 +1584,10 @@ impl State {
             label: Some("Render Encoder"),
         });
         
+        for &(pane, rect) in &pane_rects {
+            if let Some(mode) = self.density_mode(pane) { self.update_density(&mut encoder, mode, rect) }
+        }
+        
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Render Pass"),
             color_attachments: &[
@@ -464,20 +1610,98 @@ impl State {
             timestamp_writes: None,
         });
         
-        render_pass.set_pipeline(&self.render_pipeline);
-        render_pass.set_bind_group(0, &self.bind_group, &[]);
-        render_pass.set_bind_group(1, &self.uniform_bind_group, &[]);
-        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-        render_pass.draw_indexed(0..(teapot::INDICES.len() as u32 * 3), 0, 0..1);
+        for &(pane, rect) in &pane_rects {
+            render_pass.set_viewport(rect.x as f32, rect.y as f32, rect.width as f32, rect.height as f32, 0.0, 1.0);
+            render_pass.set_bind_group(0, &self.bind_group, &[]);
+            match (self.precision(pane), &self.perturbation, &self.orbits[pane as usize]) {
+                _ if let Some(density) = &self.density && self.density_mode(pane).is_some() => {
+                    render_pass.set_pipeline(&self.density_pipeline);
+                    render_pass.set_bind_group(0, &density.display_bind_group, &[]);
+                }
+                (Precision::Perturbation, Some(perturbation), Some(binding)) => {
+                    render_pass.set_pipeline(&perturbation.pipeline);
+                    render_pass.set_bind_group(2, &binding.bind_group, &[]);
+                    render_pass.set_bind_group(3, &perturbation.glitch_bind_group, &[]);
+                }
+                _ => render_pass.set_pipeline(&self.render_pipeline),
+            }
+            render_pass.set_bind_group(1, &self.uniform_bind_groups[pane as usize], &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        drop(render_pass);
         
-        self.text_renderer.render(&self.atlas, &self.viewport, &mut render_pass).unwrap();
+        // The flags get copied before the fixup pass touches them, so the check sees what the main orbit glitched on
+        let checking_glitches = !glitch_checks.is_empty();
+        if let Some(perturbation) = &self.perturbation && checking_glitches {
+            let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Glitch readback"),
+                size: perturbation.glitch_buffer.size(),
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            encoder.copy_buffer_to_buffer(&perturbation.glitch_buffer, 0, &buffer, 0, perturbation.glitch_buffer.size());
+            self.glitch_readback = Some(GlitchReadback { buffer, mapped: Default::default(), surface_width: self.config.width, panes: glitch_checks });
+        }
         
-        drop(render_pass);
+        // A separate pass, since the flags written by the first one aren't guaranteed to be visible within it
+        let fixups = pane_rects.iter().filter_map(|&(pane, rect)| {
+            let binding = self.secondary_orbits[pane as usize].as_ref()?;
+            (self.precision(pane) == Precision::Perturbation).then_some((pane, rect, binding))
+        }).collect::<Vec<_>>();
+        if let Some(perturbation) = &self.perturbation && !fixups.is_empty() {
+            let mut fixup_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Glitch fixup pass"),
+                color_attachments: &[
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: &view,
+                        resolve_target: None,
+                        ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store },
+                    }),
+                ],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            
+            fixup_pass.set_pipeline(&perturbation.fixup_pipeline);
+            fixup_pass.set_bind_group(0, &self.bind_group, &[]);
+            fixup_pass.set_bind_group(3, &perturbation.glitch_bind_group, &[]);
+            for &(pane, rect, binding) in &fixups {
+                fixup_pass.set_viewport(rect.x as f32, rect.y as f32, rect.width as f32, rect.height as f32, 0.0, 1.0);
+                fixup_pass.set_bind_group(1, &self.uniform_bind_groups[pane as usize], &[]);
+                fixup_pass.set_bind_group(2, &binding.bind_group, &[]);
+                fixup_pass.draw(0..3, 0..1);
+            }
+        }
+        
+        let mut text_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Text pass"),
+            color_attachments: &[
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store },
+                }),
+            ],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        
+        self.text_renderer.render(&self.atlas, &self.viewport, &mut text_pass).unwrap();
+        
+        drop(text_pass);
         
         self.queue.submit(std::iter::once(encoder.finish()));
         output.present();
         
+        if let Some(readback) = &self.glitch_readback && checking_glitches {
+            let mapped = readback.mapped.clone();
+            readback.buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| { mapped.set(result.is_ok()).ok(); });
+        }
+        // Lets the readback's callback run on native, the browser does that on its own
+        self.device.poll(wgpu::PollType::Poll).ok();
+        
         self.atlas.trim();
         
         Ok(())
@@ -485,22 +1709,34 @@ impl State {
 }
 
 
+// Whatever reaches the app from outside the event loop, which on the web is everything async
+pub enum UserEvent {
+    // The state once it's done setting up
+    Ready(Box<State>),
+    // A png dropped onto the canvas, for image traps
+    TrapImage(Vec<u8>),
+}
+
 pub struct App {
     state: Option<State>,
+    // A location to start the mandelbrot view at, in the form FractalParams::location writes
+    pub start_location: Option<String>,
     #[cfg(target_arch = "wasm32")]
-    proxy: Option<winit::event_loop::EventLoopProxy<State>>,
+    proxy: Option<winit::event_loop::EventLoopProxy<UserEvent>>,
 }
 
 impl App {
-    pub fn new(#[cfg(target_arch = "wasm32")] event_loop: &EventLoop<State>) -> Self {
+    #[allow(clippy::new_without_default)]
+    pub fn new(#[cfg(target_arch = "wasm32")] event_loop: &EventLoop<UserEvent>) -> Self {
         Self {
             state: None,
+            start_location: None,
             #[cfg(target_arch = "wasm32")] proxy: Some(event_loop.create_proxy()),
         }
     }
 }
 
-impl ApplicationHandler<State> for App {
+impl ApplicationHandler<UserEvent> for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         #[allow(unused_mut)]
         let mut window_attributes = Window::default_attributes();
@@ -513,25 +1749,38 @@ impl ApplicationHandler<State> for App {
         let window = Arc::new(event_loop.create_window(window_attributes).unwrap());
         
         #[cfg(not(target_arch = "wasm32"))] {
-            self.state = Some(pollster::block_on(State::new(window)).unwrap());
+            let mut state = pollster::block_on(State::new(window)).unwrap();
+            if let Some(location) = &self.start_location
+                && let Err(e) = state.mandelbrot.set_location(location) {
+                log::error!("Couldn't go to {location:?}: {e}");
+            }
+            self.state = Some(state);
         }
         
         #[cfg(target_arch = "wasm32")]
         if let Some(proxy) = self.proxy.take() {
+            canvas::forward_dropped_files(proxy.clone());
             wasm_bindgen_futures::spawn_local(async move {
-                assert!(proxy.send_event(State::new(window).await.expect("Unable to create canvas.")).is_ok())
+                assert!(proxy.send_event(UserEvent::Ready(Box::new(State::new(window).await.expect("Unable to create canvas.")))).is_ok())
             })
         }
     }
     
-    #[allow(unused_mut)]
-    fn user_event(&mut self, _event_loop: &ActiveEventLoop, mut event: State) {
-        #[cfg(target_arch = "wasm32")] {
-            event.window.request_redraw();
-            event.resize(event.window.inner_size());
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: UserEvent) {
+        match event {
+            #[allow(unused_mut)]
+            UserEvent::Ready(mut state) => {
+                #[cfg(target_arch = "wasm32")] {
+                    state.window.request_redraw();
+                    state.resize(state.window.inner_size());
+                }
+                
+                self.state = Some(*state);
+            }
+            UserEvent::TrapImage(bytes) => if let Some(state) = &mut self.state {
+                state.message = state.set_trap_image(&bytes).err().map(|e| format!("{e}"));
+            }
         }
-        
-        self.state = Some(event);
     }
     
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _window_id: WindowId, event: WindowEvent) {
@@ -549,16 +1798,113 @@ impl ApplicationHandler<State> for App {
                 Err(e) => log::error!("Render broke uh oh: {e}")
             }
             
+            WindowEvent::KeyboardInput { event, .. } if state.text_input.is_some() => state.type_key(&event),
+            WindowEvent::DroppedFile(path) => {
+                let result = std::fs::read(&path).map_err(Error::from).and_then(|bytes| state.set_trap_image(&bytes));
+                state.message = result.err().map(|e| format!("{}: {e}", path.display()));
+            }
             WindowEvent::KeyboardInput {
-                event: KeyEvent { physical_key: PhysicalKey::Code(code), state, .. }, ..
-            } => match (code, state.is_pressed()) {
+                event: KeyEvent { physical_key: PhysicalKey::Code(code), state: key_state, .. }, ..
+            } => match (code, key_state.is_pressed()) {
                 (KeyCode::Escape, true) => event_loop.exit(),
+                (KeyCode::BracketLeft | KeyCode::BracketRight, true) => if let Some(pane) = state.pane_at(state.mouse_position) {
+                    let params = state.pane_params_mut(pane);
+                    params.max_iterations = if code == KeyCode::BracketLeft { (params.max_iterations / 2).max(16) } else { params.max_iterations.saturating_mul(2) };
+                }
+                (KeyCode::KeyJ, true) => state.julia_mode = !state.julia_mode,
+                (KeyCode::KeyP, true) => state.precision_mode = state.precision_mode.next(),
+                (KeyCode::KeyB, true) => state.use_bla = !state.use_bla,
+                // Both panes always show the same fractal type, one as the parameter space and one as a julia set
+                (KeyCode::KeyF, true) => {
+                    let fractal = state.mandelbrot.fractal.next();
+                    state.mandelbrot.set_fractal(fractal);
+                    state.julia.set_fractal(fractal);
+                }
+                (KeyCode::Semicolon | KeyCode::Quote, true) => {
+                    let exponent = if code == KeyCode::Semicolon { state.mandelbrot.exponent - 0.25 } else { state.mandelbrot.exponent + 0.25 };
+                    state.mandelbrot.exponent = exponent.clamp(-8.0, 8.0);
+                    state.julia.exponent = exponent.clamp(-8.0, 8.0);
+                }
+                (KeyCode::KeyN, true) => state.text_input = Some(TextInput { kind: InputKind::Polynomial, text: String::new() }),
+                (KeyCode::KeyT, true) => state.text_input = Some(TextInput { kind: InputKind::Formula, text: String::new() }),
+                (KeyCode::KeyD, true) => state.density_mode = match state.density_mode {
+                    None => Some(DensityMode::Buddhabrot),
+                    Some(DensityMode::Buddhabrot) => Some(DensityMode::Nebulabrot),
+                    Some(DensityMode::Nebulabrot) => None,
+                },
+                (KeyCode::KeyS, true) => state.text_input = Some(TextInput { kind: InputKind::Sequence, text: String::new() }),
+                (KeyCode::Digit9 | KeyCode::Digit0, true) => {
+                    let step = if code == KeyCode::Digit9 { -0.1 } else { 0.1 };
+                    // Rounded so repeated steps don't pile up float error
+                    let relaxation = Complex::new(((state.mandelbrot.relaxation.re + step) * 10.0).round().clamp(1.0, 20.0) / 10.0, state.mandelbrot.relaxation.im);
+                    state.mandelbrot.relaxation = relaxation;
+                    state.julia.relaxation = relaxation;
+                }
+                // Independent of the exterior coloring, so any of them goes with any of these
+                (KeyCode::KeyI, true) => {
+                    let interior = state.mandelbrot.interior.next();
+                    state.mandelbrot.interior = interior;
+                    state.julia.interior = interior;
+                }
+                (KeyCode::KeyO, true) => {
+                    state.mandelbrot.trap.next_shape();
+                    state.julia.trap.shape = state.mandelbrot.trap.shape;
+                }
+                (KeyCode::KeyK, true) => state.text_input = Some(TextInput { kind: InputKind::Trap, text: String::new() }),
+                (KeyCode::KeyU, true) => state.text_input = Some(state.misiurewicz_input()),
+                (KeyCode::KeyM, true) => if let Err(e) = state.zoom_to_minibrot() { state.message = Some(format!("{e}")) },
+                (KeyCode::KeyC, true) => {
+                    let color_mode = state.mandelbrot.color_mode.next();
+                    state.mandelbrot.color_mode = color_mode;
+                    state.julia.color_mode = color_mode;
+                }
+                // Smooth coloring looks better the further out the bailout is
+                (KeyCode::Comma | KeyCode::Period, true) => if let Some(pane) = state.pane_at(state.mouse_position) {
+                    let params = state.pane_params_mut(pane);
+                    params.bailout = if code == KeyCode::Comma { (params.bailout / 2.0).max(2.0) } else { (params.bailout * 2.0).min(1e8) };
+                }
+                (KeyCode::Minus | KeyCode::Equal, true) => if let Some(pane) = state.pane_at(state.mouse_position) {
+                    let params = state.pane_params_mut(pane);
+                    params.line_thickness = if code == KeyCode::Minus { (params.line_thickness / 1.5).max(0.25) } else { (params.line_thickness * 1.5).min(64.0) };
+                }
+                (KeyCode::KeyL, true) => for pane in state.visible_panes() {
+                    log::info!("{pane:?} location: {}", state.pane_params(pane).location());
+                }
+                (KeyCode::Tab, true) => state.layout = match state.layout {
+                    Layout::Single => Layout::Split,
+                    Layout::Split => Layout::Single,
+                },
+                (KeyCode::Space, true) if !state.picking_julia && state.pane_at(state.mouse_position) == Some(Pane::Mandelbrot) => {
+                    state.picking_julia = true;
+                    state.pick_julia();
+                }
+                (KeyCode::Space, false) => state.picking_julia = false,
                 _ => ()
             }
             
             WindowEvent::CursorMoved { position, device_id: _ } => {
+                if let Some(pane) = state.dragging {
+                    state.pan(pane, position.x - state.mouse_position.x, position.y - state.mouse_position.y);
+                }
                 state.mouse_position = position;
-                state.queue.write_buffer(&state.uniform_buffer, 0, bytemuck::cast_slice(&[state.mouse_position.x as f32 / state.config.width as f32, state.mouse_position.y as f32 / state.config.height as f32]));
+                let over_mandelbrot = state.pane_at(position) == Some(Pane::Mandelbrot);
+                if state.picking_julia || (state.layout == Layout::Split && over_mandelbrot) { state.pick_julia() }
+            }
+            
+            WindowEvent::MouseInput { state: button_state, button: MouseButton::Left, .. } => {
+                state.dragging = if button_state.is_pressed() { state.pane_at(state.mouse_position) } else { None };
+            }
+            
+            WindowEvent::MouseInput { state: button_state, button: MouseButton::Right, .. } if button_state.is_pressed() && state.pane_at(state.mouse_position) == Some(Pane::Mandelbrot) => {
+                state.pick_julia();
+            }
+            
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y as f64,
+                    MouseScrollDelta::PixelDelta(position) => position.y / 50.0,
+                };
+                state.zoom(0.8f64.powf(lines));
             }
             
             _ => ()
@@ -574,6 +1920,7 @@ pub fn run() -> Result<()> {
     
     let event_loop = EventLoop::with_user_event().build()?;
     let mut app = App::new();
+    app.start_location = std::env::args().nth(1);
     
     event_loop.run_app(&mut app)?;
     Ok(())
@@ -598,3 +1945,98 @@ pub fn run() -> std::result::Result<(), wasm_bindgen::JsValue> {
 
 
 
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // The offsets of the members of the struct called name in a shader, and its size
+    fn shader_layout(source: &str, name: &str) -> (Vec<(String, u32)>, u32) {
+        let module = naga::front::wgsl::parse_str(source).unwrap();
+        module.types.iter().find_map(|(_, ty)| match &ty.inner {
+            naga::TypeInner::Struct { members, span } if ty.name.as_deref() == Some(name) => {
+                Some((members.iter().map(|member| (member.name.clone().unwrap_or_default(), member.offset)).collect(), *span))
+            }
+            _ => None,
+        }).unwrap()
+    }
+    
+    // Bytemuck only checks there's no padding on the rust side, this checks it lines up with where wgsl puts things
+    #[test]
+    fn uniforms_match_the_shaders() {
+        let (members, size) = shader_layout(concat!(include_str!("df64.wgsl"), include_str!("shader.wgsl")), "Uniforms");
+        let offset = |name: &str| members.iter().find(|(member, _)| member == name).unwrap().1 as usize;
+        assert_eq!(std::mem::size_of::<FractalUniforms>(), size as usize);
+        assert_eq!(std::mem::offset_of!(FractalUniforms, sequence_length), offset("sequence_length"));
+        assert_eq!(std::mem::offset_of!(FractalUniforms, trap_center), offset("trap_center"));
+        assert_eq!(std::mem::offset_of!(FractalUniforms, interior_mode), offset("interior_mode"));
+        assert_eq!(std::mem::offset_of!(FractalUniforms, newton), offset("newton"));
+        
+        let (members, size) = shader_layout(include_str!("density.wgsl"), "DensityUniforms");
+        assert_eq!(std::mem::size_of::<DensityUniforms>(), size as usize);
+        assert_eq!(std::mem::offset_of!(DensityUniforms, limits), members.last().unwrap().1 as usize);
+    }
+    
+    // find_bla() in perturbation.wgsl reads level k's entry j from level_starts[k] + j, for j < len >> k
+    #[test]
+    fn bla_header_indexes_the_entries() {
+        let params = FractalParams { max_iterations: 300, ..Default::default() };
+        let orbit = ReferenceOrbit::compute(Vec2(BigFloat::from_f64(-0.12, 2), BigFloat::from_f64(0.75, 2)), &params);
+        let table = BlaTable::compute(&orbit, FloatExp::from_f64(1e-10));
+        let header = BlaHeader::new(&table);
+        let entries = table.gpu_entries();
+        assert_eq!(header.levels as usize, table.levels.len());
+        for (level, blas) in table.levels.iter().enumerate() {
+            assert_eq!(blas.len(), (header.len >> level) as usize);
+            for (index, bla) in blas.iter().enumerate() {
+                let entry = entries[(header.level_starts[level] + index as u32) as usize];
+                assert_eq!(entry.a, [bla.a.0 as f32, bla.a.1 as f32]);
+                assert_eq!(entry.b, [bla.b.0 as f32, bla.b.1 as f32]);
+            }
+        }
+        let last = table.levels.len() - 1;
+        assert_eq!(header.level_starts[last] as usize + table.levels[last].len(), entries.len());
+    }
+    
+    // Flags for the whole surface, laid out like glitch_index() in perturbation.wgsl does
+    fn glitch_flags(surface_width: u32, surface_height: u32, glitched: &[(u32, u32)]) -> Vec<u32> {
+        let mut flags = vec![0; (surface_width * surface_height) as usize];
+        for &(x, y) in glitched { flags[(y * surface_width + x) as usize] = 1 }
+        flags
+    }
+    
+    #[test]
+    fn glitched_pixels_land_inside_the_glitch() {
+        let whole = Rect { x: 0, y: 0, width: 10, height: 6 };
+        assert_eq!(pick_glitched_pixel(&glitch_flags(10, 6, &[]), 10, whole), None);
+        
+        let blob = (2..5).flat_map(|x| (1..4).map(move |y| (x, y))).collect::<Vec<_>>();
+        let picked = pick_glitched_pixel(&glitch_flags(10, 6, &blob), 10, whole).unwrap();
+        assert!(blob.contains(&picked));
+        assert_eq!(picked, (3, 2));
+        
+        // The right pane of a split only looks at its own half, with rows still the whole surface wide
+        let right = Rect { x: 5, y: 0, width: 5, height: 6 };
+        let flags = glitch_flags(10, 6, &[(1, 1), (2, 1), (7, 4), (8, 4), (8, 5)]);
+        assert_eq!(pick_glitched_pixel(&flags, 10, right), Some((8, 4)));
+        assert_eq!(pick_glitched_pixel(&flags, 10, Rect { x: 0, y: 0, width: 5, height: 6 }).map(|(_, y)| y), Some(1));
+    }
+    
+    #[test]
+    fn zoom_animations_stop_when_the_view_changes_under_them() {
+        let mut params = FractalParams::default();
+        let target = Vec2(BigFloat::from_f64(-1.75, 2), BigFloat::from_f64(0.0, 2));
+        let mut animation = ZoomAnimation::new(&params, Pane::Mandelbrot, target, FloatExp::from_f64(0.05));
+        animation.elapsed = 0.1;
+        animation.apply(&mut params);
+        assert!(!animation.interrupted(&params));
+        
+        let mut panned = params.clone();
+        panned.shift_center(Vec2(FloatExp::from_f64(0.01), FloatExp::ZERO));
+        assert!(animation.interrupted(&panned));
+        // Newton keeps the view, but the target was only ever a mandelbrot point
+        params.set_fractal(FractalType::Newton);
+        assert!(animation.interrupted(&params));
+    }
+}
@@ -1,6 +1,6 @@
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
-pub struct Vec2<T: Copy>(pub T, pub T);
+pub struct Vec2<T>(pub T, pub T);
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct Vec3<T: Copy>(pub T, pub T, pub T);
@@ -17,3 +17,1042 @@ pub struct Mat3<T: Copy>(pub Vec3<T>, pub Vec3<T>, pub Vec3<T>);
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct Mat4<T: Copy>(pub Vec4<T>, pub Vec4<T>, pub Vec4<T>, pub Vec4<T>);
+
+
+// Double-float: an unevaluated sum of two f32s, good for about 48 bits of mantissa on hardware that only does f32.
+// The algorithms are the usual Dekker/Knuth ones and must stay in step with df64.wgsl.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Df64 {
+    pub hi: f32,
+    pub lo: f32,
+}
+
+impl Df64 {
+    pub const ZERO: Self = Self { hi: 0.0, lo: 0.0 };
+    
+    pub fn from_f64(x: f64) -> Self {
+        let hi = x as f32;
+        Self { hi, lo: (x - hi as f64) as f32 }
+    }
+    
+    pub fn to_f64(self) -> f64 {
+        self.hi as f64 + self.lo as f64
+    }
+    
+    fn two_sum(a: f32, b: f32) -> Self {
+        let s = a + b;
+        let v = s - a;
+        Self { hi: s, lo: (a - (s - v)) + (b - v) }
+    }
+    
+    // Only exact when |a| >= |b|
+    fn quick_two_sum(a: f32, b: f32) -> Self {
+        let s = a + b;
+        Self { hi: s, lo: b - (s - a) }
+    }
+    
+    // Splits into two halves of 12 bits so their products are exact. No fma here since webgl doesn't have one.
+    fn split(a: f32) -> (f32, f32) {
+        let t = 4097.0 * a;
+        let hi = t - (t - a);
+        (hi, a - hi)
+    }
+    
+    fn two_prod(a: f32, b: f32) -> Self {
+        let p = a * b;
+        let (a_hi, a_lo) = Self::split(a);
+        let (b_hi, b_lo) = Self::split(b);
+        Self { hi: p, lo: ((a_hi * b_hi - p) + a_hi * b_lo + a_lo * b_hi) + a_lo * b_lo }
+    }
+    
+    pub fn square(self) -> Self {
+        self * self
+    }
+}
+
+impl std::ops::Add for Df64 {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        let s = Self::two_sum(self.hi, other.hi);
+        let t = Self::two_sum(self.lo, other.lo);
+        let s = Self::quick_two_sum(s.hi, s.lo + t.hi);
+        Self::quick_two_sum(s.hi, s.lo + t.lo)
+    }
+}
+
+impl std::ops::Neg for Df64 {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self { hi: -self.hi, lo: -self.lo }
+    }
+}
+
+impl std::ops::Sub for Df64 {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        self + -other
+    }
+}
+
+impl std::ops::Mul for Df64 {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        let p = Self::two_prod(self.hi, other.hi);
+        Self::quick_two_sum(p.hi, p.lo + (self.hi * other.lo + self.lo * other.hi))
+    }
+}
+
+
+
+// Arbitrary precision number for deep zoom coordinates. Under the hood it's really fixed point: a sign and a magnitude
+// with `fraction_limbs` 32 bit limbs after the binary point and however many it needs before. Fractal coordinates never
+// get big, so that's all the range there is any use for, and it means nothing is ever rounded except products.
+#[derive(Debug, Clone)]
+pub struct BigFloat {
+    negative: bool,
+    // Little endian magnitude
+    limbs: Vec<u32>,
+    fraction_limbs: usize,
+}
+
+const LOG2_10: f64 = std::f64::consts::LOG2_10;
+
+impl BigFloat {
+    pub fn zero(fraction_limbs: usize) -> Self {
+        Self { negative: false, limbs: vec![0; fraction_limbs], fraction_limbs }
+    }
+    
+    // Enough bits to resolve pixels at this scale, plus a healthy margin for the iteration to eat into
+    pub fn limbs_for_scale(scale: FloatExp) -> usize {
+        ((64.0 - scale.log2().min(0.0)) / 32.0).ceil() as usize
+    }
+    
+    pub fn fraction_limbs(&self) -> usize {
+        self.fraction_limbs
+    }
+    
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+    
+    fn normalize(mut self) -> Self {
+        while self.limbs.len() > self.fraction_limbs && self.limbs.last() == Some(&0) { self.limbs.pop(); }
+        if self.limbs.iter().all(|&limb| limb == 0) { self.negative = false }
+        self
+    }
+    
+    // Extends with zeros or truncates towards zero
+    pub fn with_precision(&self, fraction_limbs: usize) -> Self {
+        let mut limbs = self.limbs.clone();
+        if fraction_limbs >= self.fraction_limbs {
+            limbs.splice(0..0, std::iter::repeat_n(0, fraction_limbs - self.fraction_limbs));
+        } else {
+            limbs.drain(0..self.fraction_limbs - fraction_limbs);
+        }
+        Self { negative: self.negative, limbs, fraction_limbs }.normalize()
+    }
+    
+    pub fn from_f64(x: f64, fraction_limbs: usize) -> Self {
+        Self::from_float_exp(FloatExp::from_f64(x), fraction_limbs)
+    }
+    
+    pub fn from_float_exp(x: FloatExp, fraction_limbs: usize) -> Self {
+        if x.mantissa == 0.0 || !x.mantissa.is_finite() { return Self::zero(fraction_limbs) }
+        
+        // The mantissa has 53 bits, so this is exact
+        let mantissa = (x.mantissa.abs() * (1u64 << 53) as f64) as u64;
+        let exponent = x.exponent as i64 - 53;
+        
+        // |x| = mantissa * 2^exponent, and what gets stored is |x| * 2^(32 * fraction_limbs)
+        let shift = exponent + 32 * fraction_limbs as i64;
+        let mantissa = [mantissa as u32, (mantissa >> 32) as u32];
+        let mut limbs = if shift >= 0 { shift_left(&mantissa, shift as usize) } else { shift_right(&mantissa, -shift as usize) };
+        limbs.resize(limbs.len().max(fraction_limbs), 0);
+        Self { negative: x.mantissa < 0.0, limbs, fraction_limbs }.normalize()
+    }
+    
+    pub fn to_f64(&self) -> f64 {
+        self.to_float_exp().to_f64()
+    }
+    
+    // Rounds to nearest, with the limbs below the top three folded into a sticky bit
+    pub fn to_float_exp(&self) -> FloatExp {
+        let Some(top) = self.limbs.iter().rposition(|&limb| limb != 0) else { return FloatExp::ZERO };
+        let low = top.saturating_sub(2);
+        let mut mantissa = self.limbs[low..=top].iter().rev().fold(0u128, |mantissa, &limb| (mantissa << 32) | limb as u128);
+        if self.limbs[..low].iter().any(|&limb| limb != 0) { mantissa |= 1 }
+        
+        let magnitude = FloatExp::new(mantissa as f64, 32 * (low as i32 - self.fraction_limbs as i32));
+        if self.negative { -magnitude } else { magnitude }
+    }
+    
+    pub fn square(&self) -> Self {
+        self * self
+    }
+    
+    // Both at the precision of whichever has more
+    fn aligned(&self, other: &Self) -> (Self, Self) {
+        let fraction_limbs = self.fraction_limbs.max(other.fraction_limbs);
+        (self.with_precision(fraction_limbs), other.with_precision(fraction_limbs))
+    }
+    
+    fn from_digits(negative: bool, digits: &str, exponent: i64, fraction_limbs: usize) -> Self {
+        let mut limbs = vec![0];
+        for chunk in digits.as_bytes().chunks(9) {
+            let value = chunk.iter().fold(0, |value, digit| value * 10 + (digit - b'0') as u32);
+            let carry = mul_small(&mut limbs, 10u32.pow(chunk.len() as u32), value);
+            if carry != 0 { limbs.push(carry) }
+        }
+        for _ in 0..exponent.max(0) {
+            let carry = mul_small(&mut limbs, 10, 0);
+            if carry != 0 { limbs.push(carry) }
+        }
+        
+        // Dividing one step at a time still floors exactly, since floor(floor(x / a) / b) = floor(x / ab)
+        limbs.splice(0..0, std::iter::repeat_n(0, fraction_limbs));
+        let mut remaining = (-exponent).max(0);
+        while remaining > 0 {
+            let step = remaining.min(9);
+            div_small(&mut limbs, 10u32.pow(step as u32));
+            remaining -= step;
+        }
+        Self { negative, limbs, fraction_limbs }.normalize()
+    }
+}
+
+impl std::ops::Neg for &BigFloat {
+    type Output = BigFloat;
+    fn neg(self) -> BigFloat {
+        BigFloat { negative: !self.negative, ..self.clone() }.normalize()
+    }
+}
+
+impl std::ops::Add for &BigFloat {
+    type Output = BigFloat;
+    fn add(self, other: &BigFloat) -> BigFloat {
+        let (a, b) = self.aligned(other);
+        let (negative, limbs) = if a.negative == b.negative {
+            (a.negative, add_magnitudes(&a.limbs, &b.limbs))
+        } else if compare_magnitudes(&a.limbs, &b.limbs).is_ge() {
+            (a.negative, sub_magnitudes(&a.limbs, &b.limbs))
+        } else {
+            (b.negative, sub_magnitudes(&b.limbs, &a.limbs))
+        };
+        BigFloat { negative, limbs, fraction_limbs: a.fraction_limbs }.normalize()
+    }
+}
+
+impl std::ops::Sub for &BigFloat {
+    type Output = BigFloat;
+    fn sub(self, other: &BigFloat) -> BigFloat {
+        self + &-other
+    }
+}
+
+impl std::ops::Mul for &BigFloat {
+    type Output = BigFloat;
+    fn mul(self, other: &BigFloat) -> BigFloat {
+        let (a, b) = self.aligned(other);
+        let mut limbs = mul_magnitudes(&a.limbs, &b.limbs);
+        limbs.drain(0..a.fraction_limbs);
+        BigFloat { negative: a.negative != b.negative, limbs, fraction_limbs: a.fraction_limbs }.normalize()
+    }
+}
+
+impl Ord for BigFloat {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let (a, b) = self.aligned(other);
+        match (a.negative, b.negative) {
+            (false, true) => std::cmp::Ordering::Greater,
+            (true, false) => std::cmp::Ordering::Less,
+            (false, false) => compare_magnitudes(&a.limbs, &b.limbs),
+            (true, true) => compare_magnitudes(&b.limbs, &a.limbs),
+        }
+    }
+}
+
+impl PartialOrd for BigFloat {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for BigFloat {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for BigFloat {}
+
+// Splits anything like "-1.25", "3", ".5" or "6.02e-30" into a sign, its digits, and the power of ten the last digit is at
+fn parse_decimal(s: &str) -> crate::Result<(bool, String, i64)> {
+    let s = s.trim();
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let (mantissa, exponent) = match s.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i64>()?),
+        None => (s, 0),
+    };
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    
+    let digits = format!("{integer}{fraction}");
+    if digits.is_empty() || !digits.bytes().all(|digit| digit.is_ascii_digit()) { return Err(format!("{s:?} is not a number").into()) }
+    if exponent.abs() > 10000 { return Err(format!("exponent {exponent} is way out of range").into()) }
+    Ok((negative, digits, exponent - fraction.len() as i64))
+}
+
+// Most decimals have no exact binary form, so the magnitude gets truncated towards zero at one limb past what the
+// digits need. That's off by less than 2^-32 of the last digit's place.
+impl std::str::FromStr for BigFloat {
+    type Err = crate::Error;
+    fn from_str(s: &str) -> crate::Result<Self> {
+        let (negative, digits, exponent) = parse_decimal(s)?;
+        let fraction_limbs = ((-exponent).max(0) as f64 * LOG2_10 / 32.0).ceil() as usize + 1;
+        Ok(Self::from_digits(negative, &digits, exponent, fraction_limbs))
+    }
+}
+
+// Exact unless a precision is given, in which case it's truncated to that many decimal places
+impl std::fmt::Display for BigFloat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut integer = self.limbs[self.fraction_limbs..].to_vec();
+        let mut integer_digits = vec![];
+        while integer.iter().any(|&limb| limb != 0) || integer_digits.is_empty() {
+            integer_digits.push(b'0' + div_small(&mut integer, 10) as u8);
+        }
+        integer_digits.reverse();
+        
+        let mut fraction = self.limbs[..self.fraction_limbs].to_vec();
+        let mut fraction_digits = vec![];
+        while fraction.iter().any(|&limb| limb != 0) && f.precision().is_none_or(|precision| fraction_digits.len() < precision) {
+            fraction_digits.push(b'0' + mul_small(&mut fraction, 10, 0) as u8);
+        }
+        if let Some(precision) = f.precision() { fraction_digits.resize(precision, b'0') }
+        
+        let sign = if self.negative { "-" } else if f.sign_plus() { "+" } else { "" };
+        let integer_digits = String::from_utf8(integer_digits).unwrap();
+        match fraction_digits.is_empty() {
+            true => write!(f, "{sign}{integer_digits}"),
+            false => write!(f, "{sign}{integer_digits}.{}", String::from_utf8(fraction_digits).unwrap()),
+        }
+    }
+}
+
+
+
+// A float with its exponent kept separately, for values past the end of f64's range that deep zooms get to. The
+// mantissa is always in [0.5, 1) or 0, so equal values are equal field for field. Must stay in step with floatexp.wgsl.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct FloatExp {
+    pub mantissa: f64,
+    pub exponent: i32,
+}
+
+impl FloatExp {
+    pub const ZERO: Self = Self { mantissa: 0.0, exponent: 0 };
+    pub const ONE: Self = Self { mantissa: 0.5, exponent: 1 };
+    
+    // mantissa * 2^exponent, for any mantissa
+    pub fn new(mantissa: f64, exponent: i32) -> Self {
+        if mantissa == 0.0 || !mantissa.is_finite() { return Self { mantissa, exponent: 0 } }
+        let (mantissa, shift) = frexp(mantissa);
+        Self { mantissa, exponent: exponent.saturating_add(shift) }
+    }
+    
+    pub fn from_f64(x: f64) -> Self {
+        Self::new(x, 0)
+    }
+    
+    // Underflows to 0 and overflows to infinity outside of f64's range
+    pub fn to_f64(self) -> f64 {
+        let exponent = self.exponent.clamp(-2200, 2200);
+        self.mantissa * 2f64.powi(exponent / 2) * 2f64.powi(exponent - exponent / 2)
+    }
+    
+    pub fn abs(self) -> Self {
+        Self { mantissa: self.mantissa.abs(), ..self }
+    }
+    
+    pub fn is_zero(self) -> bool {
+        self.mantissa == 0.0
+    }
+    
+    pub fn log2(self) -> f64 {
+        self.mantissa.abs().log2() + self.exponent as f64
+    }
+    
+    pub fn log10(self) -> f64 {
+        self.log2() * std::f64::consts::LOG10_2
+    }
+    
+    pub fn sqrt(self) -> Self {
+        let (mantissa, exponent) = if self.exponent % 2 == 0 { (self.mantissa, self.exponent) } else { (self.mantissa * 2.0, self.exponent - 1) };
+        Self::new(mantissa.sqrt(), exponent / 2)
+    }
+    
+    pub fn hypot(self, other: Self) -> Self {
+        (self * self + other * other).sqrt()
+    }
+    
+    pub fn powi(self, n: i32) -> Self {
+        let mut result = Self::ONE;
+        let mut base = if n < 0 { Self::ONE / self } else { self };
+        let mut n = n.unsigned_abs();
+        while n > 0 {
+            if n & 1 == 1 { result = result * base }
+            base = base * base;
+            n >>= 1;
+        }
+        result
+    }
+    
+    pub fn min(self, other: Self) -> Self {
+        if other < self { other } else { self }
+    }
+    
+    pub fn max(self, other: Self) -> Self {
+        if other > self { other } else { self }
+    }
+}
+
+// Splits x into a mantissa in [0.5, 1) and a power of two
+fn frexp(x: f64) -> (f64, i32) {
+    if x == 0.0 || !x.is_finite() { return (x, 0) }
+    let bits = x.to_bits();
+    let biased_exponent = ((bits >> 52) & 0x7ff) as i32;
+    if biased_exponent == 0 {
+        let (mantissa, exponent) = frexp(x * 2f64.powi(64));
+        return (mantissa, exponent - 64)
+    }
+    (f64::from_bits(bits & !(0x7ff << 52) | (1022 << 52)), biased_exponent - 1022)
+}
+
+impl std::ops::Add for FloatExp {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        if self.is_zero() { return other }
+        if other.is_zero() { return self }
+        let (big, small) = if self.exponent >= other.exponent { (self, other) } else { (other, self) };
+        let shift = big.exponent as i64 - small.exponent as i64;
+        if shift > 64 { return big }
+        Self::new(big.mantissa + small.mantissa * 2f64.powi(-shift as i32), big.exponent)
+    }
+}
+
+impl std::ops::Neg for FloatExp {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self { mantissa: -self.mantissa, ..self }
+    }
+}
+
+impl std::ops::Sub for FloatExp {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        self + -other
+    }
+}
+
+impl std::ops::Mul for FloatExp {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        Self::new(self.mantissa * other.mantissa, self.exponent.saturating_add(other.exponent))
+    }
+}
+
+impl std::ops::Mul<f64> for FloatExp {
+    type Output = Self;
+    fn mul(self, other: f64) -> Self {
+        self * Self::from_f64(other)
+    }
+}
+
+impl std::ops::Div for FloatExp {
+    type Output = Self;
+    fn div(self, other: Self) -> Self {
+        Self::new(self.mantissa / other.mantissa, self.exponent.saturating_sub(other.exponent))
+    }
+}
+
+impl PartialOrd for FloatExp {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        (*self - *other).mantissa.partial_cmp(&0.0)
+    }
+}
+
+impl FloatExp {
+    // |self| in scientific notation with `precision` digits after the point, rounded half up. Goes through BigFloat,
+    // which holds it exactly, so every digit is right.
+    fn to_scientific(self, precision: usize) -> String {
+        let fraction_limbs = ((53 - self.exponent as i64).max(0) as usize).div_ceil(32) + 1;
+        let exact = BigFloat::from_float_exp(self.abs(), fraction_limbs).to_string();
+        let (integer, fraction) = exact.split_once('.').unwrap_or((&exact, ""));
+        let all_digits = format!("{integer}{fraction}");
+        let Some(first) = all_digits.bytes().position(|digit| digit != b'0') else { return format!("{:.*e}", precision, 0.0) };
+        let mut exponent = integer.len() as i64 - 1 - first as i64;
+        
+        let mut digits = all_digits.as_bytes()[first..].to_vec();
+        digits.resize(digits.len().max(precision + 2), b'0');
+        let round_up = digits[precision + 1] >= b'5';
+        digits.truncate(precision + 1);
+        if round_up {
+            match digits.iter().rposition(|&digit| digit != b'9') {
+                Some(i) => {
+                    digits[i] += 1;
+                    digits[i + 1..].fill(b'0');
+                }
+                None => {
+                    digits.fill(b'0');
+                    digits[0] = b'1';
+                    exponent += 1;
+                }
+            }
+        }
+        
+        let digits = String::from_utf8(digits).unwrap();
+        match precision {
+            0 => format!("{digits}e{exponent}"),
+            _ => format!("{}.{}e{exponent}", &digits[..1], &digits[1..]),
+        }
+    }
+}
+
+// Correctly rounded, by way of an exact BigFloat
+impl std::str::FromStr for FloatExp {
+    type Err = crate::Error;
+    fn from_str(s: &str) -> crate::Result<Self> {
+        let (negative, digits, exponent) = parse_decimal(s)?;
+        // A few limbs past the last digit so there's plenty left over to round with
+        let fraction_limbs = ((-exponent).max(0) as f64 * LOG2_10 / 32.0).ceil() as usize + 3;
+        Ok(BigFloat::from_digits(negative, &digits, exponent, fraction_limbs).to_float_exp())
+    }
+}
+
+// Inside f64's range this is just f64's formatting. Outside it, no precision means the fewest digits that read back the
+// same, like f64 does.
+impl std::fmt::LowerExp for FloatExp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let x = self.to_f64();
+        if x.is_normal() || self.is_zero() { return std::fmt::LowerExp::fmt(&x, f) }
+        
+        let written = match f.precision() {
+            Some(precision) => self.to_scientific(precision),
+            None => (0..17).map(|precision| self.to_scientific(precision)).find(|written| written.parse::<Self>().ok() == Some(self.abs())).unwrap_or_else(|| self.to_scientific(17)),
+        };
+        let sign = if self.mantissa < 0.0 { "-" } else if f.sign_plus() { "+" } else { "" };
+        write!(f, "{sign}{written}")
+    }
+}
+
+
+
+// Plain f64 complex numbers, for the things that need more than Vec2's componentwise bits
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub const ZERO: Self = Self { re: 0.0, im: 0.0 };
+    pub const ONE: Self = Self { re: 1.0, im: 0.0 };
+    
+    pub const fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+    
+    pub fn norm_squared(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+    
+    pub fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+    
+    pub fn conj(self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+    
+    pub fn powi(self, n: u32) -> Self {
+        (0..n).fold(Self::ONE, |power, _| power * self)
+    }
+    
+    pub fn arg(self) -> f64 {
+        self.im.atan2(self.re)
+    }
+    
+    pub fn exp(self) -> Self {
+        Self::new(self.im.cos(), self.im.sin()) * self.re.exp()
+    }
+    
+    // The principal branch, with the cut along the negative real axis
+    pub fn ln(self) -> Self {
+        Self::new(self.abs().ln(), self.arg())
+    }
+    
+    pub fn sqrt(self) -> Self {
+        let (r, theta) = (self.abs().sqrt(), self.arg() / 2.0);
+        Self::new(r * theta.cos(), r * theta.sin())
+    }
+    
+    // z^w for complex w, which comes out 0 at z = 0 rather than going through ln 0
+    pub fn powc(self, w: Self) -> Self {
+        if self == Self::ZERO { return self }
+        (w * self.ln()).exp()
+    }
+    
+    pub fn sin(self) -> Self {
+        Self::new(self.re.sin() * self.im.cosh(), self.re.cos() * self.im.sinh())
+    }
+    
+    pub fn cos(self) -> Self {
+        Self::new(self.re.cos() * self.im.cosh(), -self.re.sin() * self.im.sinh())
+    }
+    
+    pub fn tan(self) -> Self {
+        self.sin() / self.cos()
+    }
+    
+    pub fn sinh(self) -> Self {
+        Self::new(self.re.sinh() * self.im.cos(), self.re.cosh() * self.im.sin())
+    }
+    
+    pub fn cosh(self) -> Self {
+        Self::new(self.re.cosh() * self.im.cos(), self.re.sinh() * self.im.sin())
+    }
+    
+    pub fn tanh(self) -> Self {
+        self.sinh() / self.cosh()
+    }
+}
+
+impl std::ops::Add for Complex {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl std::ops::Neg for Complex {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self::new(-self.re, -self.im)
+    }
+}
+
+impl std::ops::Sub for Complex {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl std::ops::Mul for Complex {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        Self::new(self.re * other.re - self.im * other.im, self.re * other.im + self.im * other.re)
+    }
+}
+
+impl std::ops::Mul<f64> for Complex {
+    type Output = Self;
+    fn mul(self, other: f64) -> Self {
+        Self::new(self.re * other, self.im * other)
+    }
+}
+
+impl std::ops::Div for Complex {
+    type Output = Self;
+    fn div(self, other: Self) -> Self {
+        let numerator = self * other.conj();
+        let denominator = other.norm_squared();
+        Self::new(numerator.re / denominator, numerator.im / denominator)
+    }
+}
+
+// "1.5", "-2i", "i", "3-4.5i", "1e-3+2e2i"
+impl std::str::FromStr for Complex {
+    type Err = crate::Error;
+    fn from_str(s: &str) -> crate::Result<Self> {
+        let parse = |part: &str| part.parse::<f64>().map_err(|e| format!("bad number {part:?} in {s:?}: {e}"));
+        let Some(imaginary) = s.strip_suffix('i') else { return Ok(Self::new(parse(s)?, 0.0)) };
+        // The sign in front of the imaginary part, if there's a real part before it. Signs right after an e are exponents.
+        let split = imaginary.char_indices().skip(1).filter(|&(i, c)| (c == '+' || c == '-') && !imaginary[..i].ends_with(['e', 'E'])).last().map(|(i, _)| i);
+        let (re, im) = match split {
+            Some(i) => (parse(&imaginary[..i])?, &imaginary[i..]),
+            None => (0.0, imaginary),
+        };
+        let im = match im {
+            "" | "+" => 1.0,
+            "-" => -1.0,
+            im => parse(im)?,
+        };
+        Ok(Self::new(re, im))
+    }
+}
+
+impl std::fmt::Display for Complex {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.im == 0.0 { return write!(f, "{}", self.re) }
+        if self.re == 0.0 { return write!(f, "{}i", self.im) }
+        write!(f, "{}{:+}i", self.re, self.im)
+    }
+}
+
+
+// Polynomials are coefficient lists with the highest power first, so evaluating them is plain Horner's method
+pub fn evaluate_polynomial(coefficients: &[Complex], z: Complex) -> Complex {
+    coefficients.iter().fold(Complex::ZERO, |p, &coefficient| p * z + coefficient)
+}
+
+// Multiplies out (z - r0)(z - r1)...
+pub fn polynomial_from_roots(roots: &[Complex]) -> Vec<Complex> {
+    roots.iter().fold(vec![Complex::ONE], |coefficients, &root| {
+        let mut product = coefficients.clone();
+        product.push(Complex::ZERO);
+        for (i, &coefficient) in coefficients.iter().enumerate() {
+            product[i + 1] = product[i + 1] - coefficient * root;
+        }
+        product
+    })
+}
+
+// All the roots at once with the Durand-Kerner iteration, each root guess getting a Newton-like step against the
+// polynomial divided by every other guess. Repeated roots only converge linearly, so they come out a bit rough.
+pub fn polynomial_roots(coefficients: &[Complex]) -> crate::Result<Vec<Complex>> {
+    let leading = coefficients.iter().position(|c| *c != Complex::ZERO).ok_or("the zero polynomial has no roots to find")?;
+    let monic = coefficients[leading..].iter().map(|&c| c / coefficients[leading]).collect::<Vec<_>>();
+    let degree = monic.len() - 1;
+    
+    // Powers of something that's neither real nor a root of unity, so no two guesses start out symmetric
+    let seed = Complex::new(0.4, 0.9);
+    let mut roots = (0..degree as u32).map(|k| seed.powi(k)).collect::<Vec<_>>();
+    for _ in 0..1000 {
+        let mut change = 0f64;
+        for i in 0..degree {
+            let denominator = (0..degree).filter(|&j| j != i).fold(Complex::ONE, |d, j| d * (roots[i] - roots[j]));
+            let step = evaluate_polynomial(&monic, roots[i]) / denominator;
+            roots[i] = roots[i] - step;
+            change = change.max(step.abs());
+        }
+        if change < 1e-15 { break }
+    }
+    if roots.iter().any(|root| !root.re.is_finite() || !root.im.is_finite()) { return Err("root finding blew up".into()) }
+    Ok(roots)
+}
+
+
+// Little endian big integer helpers for BigFloat
+
+fn compare_magnitudes(a: &[u32], b: &[u32]) -> std::cmp::Ordering {
+    for i in (0..a.len().max(b.len())).rev() {
+        let ordering = a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0));
+        if ordering.is_ne() { return ordering }
+    }
+    std::cmp::Ordering::Equal
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0..a.len().max(b.len()) {
+        let sum = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        result.push(sum as u32);
+        carry = sum >> 32;
+    }
+    result.push(carry as u32);
+    result
+}
+
+// Needs a >= b
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &limb) in a.iter().enumerate() {
+        let difference = limb as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        result.push(difference.rem_euclid(1 << 32) as u32);
+        borrow = (difference < 0) as i64;
+    }
+    result
+}
+
+fn mul_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let product = x as u64 * y as u64 + result[i + j] as u64 + carry;
+            result[i + j] = product as u32;
+            carry = product >> 32;
+        }
+        result[i + b.len()] = carry as u32;
+    }
+    result
+}
+
+// a = a * factor + addend, returning whatever carried out the top
+fn mul_small(a: &mut [u32], factor: u32, addend: u32) -> u32 {
+    let mut carry = addend as u64;
+    for limb in a.iter_mut() {
+        let product = *limb as u64 * factor as u64 + carry;
+        *limb = product as u32;
+        carry = product >> 32;
+    }
+    carry as u32
+}
+
+// a = a / divisor, returning the remainder
+fn div_small(a: &mut [u32], divisor: u32) -> u32 {
+    let mut remainder = 0u64;
+    for limb in a.iter_mut().rev() {
+        let value = (remainder << 32) | *limb as u64;
+        *limb = (value / divisor as u64) as u32;
+        remainder = value % divisor as u64;
+    }
+    remainder as u32
+}
+
+fn shift_left(a: &[u32], bits: usize) -> Vec<u32> {
+    let mut result = vec![0u32; bits / 32];
+    result.extend_from_slice(a);
+    result.push(0);
+    let shift = bits % 32;
+    if shift > 0 {
+        for i in (bits / 32..result.len()).rev() {
+            result[i] = (result[i] << shift) | result.get(i.wrapping_sub(1)).filter(|_| i > bits / 32).map_or(0, |&low| low >> (32 - shift));
+        }
+    }
+    result
+}
+
+fn shift_right(a: &[u32], bits: usize) -> Vec<u32> {
+    let skip = bits / 32;
+    let shift = bits % 32;
+    (skip..a.len()).map(|i| {
+        let high = a.get(i + 1).map_or(0, |&high| if shift > 0 { high << (32 - shift) } else { 0 });
+        (a[i] >> shift) | high
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // Double-float keeps about 48 bits, so anything within a few units of 2^-44 relative is right
+    fn assert_close(actual: Df64, expected: f64) {
+        let error = (actual.to_f64() - expected).abs();
+        assert!(error <= expected.abs() * 1e-13 + 1e-40, "got {actual:?} = {}, expected {expected}", actual.to_f64());
+    }
+    
+    // Same little lcg every time so failures are reproducible
+    fn values() -> Vec<f64> {
+        let mut state = 0x2545f4914f6cdd1du64;
+        let mut values = vec![0.0, 1.0, -1.0, 0.1, -0.75, 1.0 / 3.0, std::f64::consts::PI, -1.7490096027, 2.0f64.powi(-20) + 3.0];
+        for _ in 0..200 {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let unit = (state >> 11) as f64 / (1u64 << 53) as f64;
+            values.push((unit * 8.0 - 4.0) * 10f64.powi((state % 7) as i32 - 3));
+        }
+        values
+    }
+    
+    #[test]
+    fn df64_round_trips_f64() {
+        for x in values() {
+            assert_close(Df64::from_f64(x), x);
+        }
+    }
+    
+    #[test]
+    fn df64_arithmetic_matches_f64() {
+        let values = values();
+        for pair in values.windows(2) {
+            let (a, b) = (Df64::from_f64(pair[0]), Df64::from_f64(pair[1]));
+            let (a64, b64) = (a.to_f64(), b.to_f64());
+            assert_close(a + b, a64 + b64);
+            assert_close(a - b, a64 - b64);
+            assert_close(a * b, a64 * b64);
+            assert_close(a.square(), a64 * a64);
+        }
+    }
+    
+    #[test]
+    fn df64_keeps_bits_f32_loses() {
+        let a = Df64::from_f64(1.0);
+        let b = Df64::from_f64(1e-10);
+        assert_eq!(1.0f32 + 1e-10f32, 1.0);
+        // The small part only survives to 48 bits relative to the big one
+        assert!(((a + b - a).to_f64() - 1e-10).abs() < 1e-14);
+    }
+    
+    
+    fn big(x: &str) -> BigFloat {
+        x.parse().unwrap()
+    }
+    
+    #[test]
+    fn big_float_matches_f64() {
+        let values = values();
+        for pair in values.windows(2) {
+            let (a, b) = (BigFloat::from_f64(pair[0], 4), BigFloat::from_f64(pair[1], 4));
+            assert_eq!(a.to_f64(), pair[0]);
+            assert_eq!((&a + &b).to_f64(), pair[0] + pair[1]);
+            assert_eq!((&a - &b).to_f64(), pair[0] - pair[1]);
+            assert!(((&a * &b).to_f64() - pair[0] * pair[1]).abs() <= (pair[0] * pair[1]).abs() * 1e-15);
+            assert_eq!(a.cmp(&b), pair[0].partial_cmp(&pair[1]).unwrap());
+        }
+    }
+    
+    #[test]
+    fn big_float_keeps_tiny_differences() {
+        let one = BigFloat::from_f64(1.0, 8);
+        let tiny = big("1e-60");
+        let sum = &one + &tiny;
+        assert!(sum > one);
+        assert!(((&sum - &one).to_f64() / 1e-60 - 1.0).abs() < 1e-12);
+        assert_eq!((&sum - &sum).to_f64(), 0.0);
+        assert_eq!(BigFloat::from_f64(1e-30, 8).to_f64(), 1e-30);
+        assert_eq!(BigFloat::from_f64(1.0 + 2f64.powi(-40), 1).to_f64(), 1.0);
+        assert_eq!(big("0.5").square(), big("0.25"));
+        assert_eq!(&big("-1.5") * &big("2"), big("-3"));
+    }
+    
+    #[test]
+    fn big_float_parses_and_formats() {
+        assert_eq!(big("-1.25").to_string(), "-1.25");
+        assert_eq!(big("+3").to_string(), "3");
+        assert_eq!(big(".5").to_string(), "0.5");
+        assert_eq!(big("2.5e2").to_string(), "250");
+        assert_eq!(big("-0").to_string(), "0");
+        assert_eq!(format!("{:.3}", big("-0.74364388")), "-0.743");
+        assert_eq!(format!("{:+.2}", big("0.5")), "+0.50");
+        assert!("1.2.3".parse::<BigFloat>().is_err());
+        assert!("".parse::<BigFloat>().is_err());
+        assert!("e5".parse::<BigFloat>().is_err());
+    }
+    
+    #[test]
+    fn big_float_round_trips_through_strings() {
+        for s in ["-0.743643887037158704752191506114774", "0.131825904205311970493132056385139", "1e-100", "-1.7490096027e-45"] {
+            let x = big(s);
+            let y = big(&x.to_string());
+            assert_eq!(x, y);
+            assert_eq!(x.to_string(), y.to_string());
+        }
+        let x = &BigFloat::from_f64(-0.75, 10) + &big("1e-80");
+        assert_eq!(big(&x.to_string()), x);
+    }
+    
+    // Within a couple of f64 roundings of the exact answer
+    fn assert_float_exp_close(actual: FloatExp, expected: &BigFloat) {
+        let expected = expected.to_float_exp();
+        let error = (actual - expected).abs();
+        assert!(error <= expected.abs() * 1e-15, "got {actual:e}, expected {expected:e}");
+    }
+    
+    // The same values as everywhere else, pushed well below where an f64 underflows
+    fn tiny_values() -> Vec<FloatExp> {
+        values().iter().enumerate().map(|(i, &x)| FloatExp::from_f64(x) * FloatExp::new(1.0, -1100 - 3 * i as i32)).collect()
+    }
+    
+    #[test]
+    fn float_exp_round_trips_through_big_float() {
+        for x in tiny_values() {
+            assert_eq!(BigFloat::from_float_exp(x, 60).to_float_exp(), x);
+            assert_eq!(x.to_f64(), 0.0);
+        }
+        for x in values() {
+            assert_eq!(FloatExp::from_f64(x).to_f64(), x);
+        }
+        assert_eq!(FloatExp::from_f64(5e-324).to_f64(), 5e-324);
+    }
+    
+    #[test]
+    fn float_exp_arithmetic_matches_big_float() {
+        let values = tiny_values();
+        for pair in values.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let (big_a, big_b) = (BigFloat::from_float_exp(a, 120), BigFloat::from_float_exp(b, 120));
+            assert_float_exp_close(a + b, &(&big_a + &big_b));
+            assert_float_exp_close(a - b, &(&big_a - &big_b));
+            assert_float_exp_close(a * b, &(&big_a * &big_b));
+            assert_eq!(a.partial_cmp(&b), Some(big_a.cmp(&big_b)));
+            if !b.is_zero() {
+                // Division has nothing to check against, but undoing it should land back where it started
+                assert_float_exp_close(a / b * b, &big_a);
+            }
+        }
+        let x = FloatExp::new(0.75, -3001);
+        assert_float_exp_close(x.sqrt() * x.sqrt(), &BigFloat::from_float_exp(x, 100));
+    }
+    
+    #[test]
+    fn float_exp_parses_and_formats() {
+        let parse = |s: &str| s.parse::<FloatExp>().unwrap();
+        assert_eq!(parse("2.5"), FloatExp::from_f64(2.5));
+        assert_eq!(parse("1.5e-10"), FloatExp::from_f64(1.5e-10));
+        assert_eq!(format!("{:e}", parse("1.5e-10")), "1.5e-10");
+        assert_eq!(format!("{:e}", parse("1e-400")), "1e-400");
+        assert_eq!(format!("{:.3e}", parse("-2.71828e-5000")), "-2.718e-5000");
+        // Parsing as a BigFloat only keeps a limb past the last digit, which isn't enough to check 53 bits against
+        let exact = |s: &str| {
+            let (negative, digits, exponent) = parse_decimal(s).unwrap();
+            BigFloat::from_digits(negative, &digits, exponent, 200)
+        };
+        assert_float_exp_close(parse("3e-1000"), &exact("3e-1000"));
+        for s in ["6.02e-1234", "9.99999e-401", "1e-308", "4e-320"] {
+            assert_float_exp_close(parse(&format!("{:e}", parse(s))), &exact(s));
+        }
+        assert_eq!(format!("{:.2e}", parse("9.999e-500")), "1.00e-499");
+        assert!("e5".parse::<FloatExp>().is_err());
+        assert!("1e".parse::<FloatExp>().is_err());
+    }
+    
+    
+    #[test]
+    fn complex_parses_and_formats() {
+        let parse = |s: &str| s.parse::<Complex>().unwrap();
+        assert_eq!(parse("1.5"), Complex::new(1.5, 0.0));
+        assert_eq!(parse("-2i"), Complex::new(0.0, -2.0));
+        assert_eq!(parse("i"), Complex::new(0.0, 1.0));
+        assert_eq!(parse("-i"), Complex::new(0.0, -1.0));
+        assert_eq!(parse("3-4.5i"), Complex::new(3.0, -4.5));
+        assert_eq!(parse("-1e-3+2e2i"), Complex::new(-1e-3, 200.0));
+        assert_eq!(parse("2e-1-i"), Complex::new(0.2, -1.0));
+        assert!("1+".parse::<Complex>().is_err());
+        assert!("x".parse::<Complex>().is_err());
+        for z in [Complex::new(3.0, -4.5), Complex::new(0.0, 2.0), Complex::new(-1.25, 0.0), Complex::new(1e-20, 3e5)] {
+            assert_eq!(parse(&z.to_string()), z);
+        }
+    }
+    
+    #[test]
+    fn polynomial_roots_come_back() {
+        // Cube roots of unity from z^3 - 1
+        let cube = [Complex::ONE, Complex::ZERO, Complex::ZERO, -Complex::ONE];
+        let roots = polynomial_roots(&cube).unwrap();
+        assert_eq!(roots.len(), 3);
+        for k in 0..3 {
+            let angle = std::f64::consts::TAU * k as f64 / 3.0;
+            let expected = Complex::new(angle.cos(), angle.sin());
+            assert!(roots.iter().any(|root| (*root - expected).abs() < 1e-12), "{expected} missing from {roots:?}");
+        }
+        
+        // Multiplying roots out and solving again gets the same roots, leading zeros and scaling aside
+        let expected = [Complex::new(1.0, 2.0), Complex::new(-0.5, 0.0), Complex::new(0.0, -1.0), Complex::new(3.0, 0.25), Complex::new(-2.0, -2.0)];
+        let mut coefficients = vec![Complex::ZERO];
+        coefficients.extend(polynomial_from_roots(&expected).into_iter().map(|c| c * 2.5));
+        let roots = polynomial_roots(&coefficients).unwrap();
+        assert_eq!(roots.len(), expected.len());
+        for expected in expected {
+            assert!(roots.iter().any(|root| (*root - expected).abs() < 1e-10), "{expected} missing from {roots:?}");
+            assert!(evaluate_polynomial(&coefficients, expected).abs() < 1e-10);
+        }
+        
+        assert!(polynomial_roots(&[Complex::ZERO, Complex::ZERO]).is_err());
+        assert_eq!(polynomial_roots(&[Complex::new(2.0, 0.0)]).unwrap(), vec![]);
+    }
+}
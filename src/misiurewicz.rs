@@ -0,0 +1,126 @@
+use crate::{BigFloat, FloatExp, MAX_NEWTON_ORBIT, NEWTON_MAX_STEPS, Vec2};
+use crate::nucleus::{big_difference, big_step, complex_div, derivative_step, newton_tolerance, newton_update, norm_squared, to_float_exp};
+
+
+// A point where 0 lands on a repelling cycle after a while, so z_(preperiod + period) = z_preperiod. The set spirals
+// around these the same way at every scale, which makes them the points to aim a symmetric zoom at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Misiurewicz {
+    pub c: Vec2<BigFloat>,
+    pub preperiod: u32,
+    pub period: u32,
+}
+
+// Returns this many times further than the closest one still count as coming back about as close
+const NEAR_CLOSEST_RETURN: f64 = 4.0;
+// Every pair of points along the orbit gets compared, so the guess only looks this far along it
+const GUESSED_ORBIT: u32 = 256;
+
+// Solves z_(preperiod + period)(c) = z_preperiod(c) with Newton's method, starting from guess. Every point with a
+// shorter preperiod solves that too, and so does every nucleus of the period, so those get divided out by way of
+// z_(i + period) - z_i for each i below the preperiod. Otherwise Newton's method would be drawn to those just as much.
+pub fn find_misiurewicz(guess: &Vec2<BigFloat>, preperiod: u32, period: u32, limbs: usize) -> crate::Result<Misiurewicz> {
+    // z_1 = z_(period + 1) would make z_period = ±z_0 = 0, which is just a nucleus
+    if preperiod < 2 || period == 0 { return Err("the preperiod has to be at least 2 and the period at least 1".into()) }
+    if preperiod + period > MAX_NEWTON_ORBIT { return Err(format!("the preperiod and period can only add up to {MAX_NEWTON_ORBIT}").into()) }
+    let tolerance = newton_tolerance(limbs);
+    let mut c = Vec2(guess.0.with_precision(limbs), guess.1.with_precision(limbs));
+    for _ in 0..NEWTON_MAX_STEPS {
+        let mut orbit = vec![(Vec2(BigFloat::zero(limbs), BigFloat::zero(limbs)), Vec2(FloatExp::ZERO, FloatExp::ZERO))];
+        for _ in 0..preperiod + period {
+            let (z, dc) = orbit.last().unwrap();
+            orbit.push((big_step(z, &c), derivative_step(to_float_exp(z), *dc)));
+        }
+        
+        // The step is 1 / (f'/f - the sum of g'/g for every g being divided out)
+        let mut sum = Vec2(FloatExp::ZERO, FloatExp::ZERO);
+        for i in 0..=preperiod as usize {
+            let ((z, dc), (z_later, dc_later)) = (&orbit[i], &orbit[i + period as usize]);
+            let difference = big_difference(z_later, z);
+            if norm_squared(difference).is_zero() {
+                if i == preperiod as usize { return Ok(Misiurewicz { c, preperiod, period }) }
+                return Err(format!("Newton's method landed on something with a preperiod under {preperiod}").into())
+            }
+            let ratio = complex_div(Vec2(dc_later.0 - dc.0, dc_later.1 - dc.1), difference);
+            sum = if i == preperiod as usize { Vec2(ratio.0 - sum.0, ratio.1 - sum.1) } else { Vec2(sum.0 + ratio.0, sum.1 + ratio.1) };
+        }
+        if norm_squared(sum).is_zero() { return Err("Newton's method landed on a critical point".into()) }
+        
+        let delta = complex_div(Vec2(FloatExp::ONE, FloatExp::ZERO), sum);
+        c = newton_update(&c, delta, limbs);
+        if norm_squared(to_float_exp(&c)) > FloatExp::from_f64(4.0) { return Err("Newton's method ran off the set".into()) }
+        if norm_squared(delta) < tolerance * tolerance { return Ok(Misiurewicz { c, preperiod, period }) }
+    }
+    Err(format!("Newton's method didn't settle on a preperiod {preperiod} period {period} point").into())
+}
+
+// Guesses the preperiod and period of the Misiurewicz point near c from c's own orbit. Close to one, the orbit lands
+// near the cycle at the preperiod and then gets pushed off it, so z_(preperiod + period) comes back to z_preperiod about
+// as close as the orbit ever comes back to itself. The shortest such return is the point's, rather than a multiple.
+pub fn misiurewicz_periods(c: &Vec2<BigFloat>, max_iterations: u32) -> Option<(u32, u32)> {
+    let limbs = c.0.fraction_limbs().max(c.1.fraction_limbs());
+    let escape = FloatExp::from_f64(4.0);
+    let mut orbit = vec![Vec2(BigFloat::zero(limbs), BigFloat::zero(limbs))];
+    for _ in 0..max_iterations.min(GUESSED_ORBIT) {
+        let z = big_step(orbit.last().unwrap(), c);
+        if norm_squared(to_float_exp(&z)) > escape { break }
+        orbit.push(z);
+    }
+    
+    // In order of where the cycle closes
+    let mut returns = Vec::new();
+    for end in 3..orbit.len() {
+        for preperiod in 2..end {
+            returns.push((preperiod as u32, (end - preperiod) as u32, norm_squared(big_difference(&orbit[end], &orbit[preperiod]))));
+        }
+    }
+    let closest = returns.iter().map(|&(_, _, distance)| distance).min_by(|a, b| a.partial_cmp(b).unwrap())?;
+    let near = closest * (NEAR_CLOSEST_RETURN * NEAR_CLOSEST_RETURN);
+    returns.into_iter().find(|&(_, _, distance)| distance <= near).map(|(preperiod, period, _)| (preperiod, period))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nucleus::tests::{assert_zero_to_precision, big, orbit};
+    
+    #[test]
+    fn misiurewicz_points_come_from_nearby_points() {
+        // -2 and i, and the real and upper preperiod 3 period 1 points, the roots of c^3 + 2c^2 + 2c + 2
+        for (guess, preperiod, period, point) in [
+            ((-1.98, 0.0), 2, 1, (-2.0, 0.0)),
+            ((0.05, 0.95), 2, 2, (0.0, 1.0)),
+            ((-1.55, 0.0), 3, 1, (-1.5436890126920764, 0.0)),
+            ((-0.2, 1.1), 3, 1, (-0.2281554936539618, 1.1151425080399373)),
+        ] {
+            let found = find_misiurewicz(&big(guess.0, guess.1, 2), preperiod, period, 2).unwrap();
+            assert!((found.c.0.to_f64() - point.0).abs() < 1e-15 && (found.c.1.to_f64() - point.1).abs() < 1e-15, "{:?}", found.c);
+        }
+    }
+    
+    #[test]
+    fn misiurewicz_points_are_exact_to_the_precision() {
+        let limbs = 8;
+        let found = find_misiurewicz(&big(-0.2, 1.1, limbs), 3, 1, limbs).unwrap();
+        let orbit = orbit(&found.c, 4);
+        assert_zero_to_precision(big_difference(&orbit[4], &orbit[3]), limbs);
+        // Strictly preperiodic, rather than a preperiod 2 point
+        assert!(norm_squared(big_difference(&orbit[3], &orbit[2])) > FloatExp::from_f64(0.1));
+        
+        assert!(find_misiurewicz(&big(-0.2, 1.1, 2), 1, 1, 2).is_err());
+    }
+    
+    #[test]
+    fn periods_come_from_the_orbit() {
+        for (c, periods) in [
+            ((-2.0 + 1e-9, 1e-9), (2, 1)),
+            ((1e-9, 1.0 - 2e-9), (2, 2)),
+            ((-0.2281554936539618 + 3e-10, 1.1151425080399373 + 1e-10), (3, 1)),
+        ] {
+            assert_eq!(misiurewicz_periods(&big(c.0, c.1, 2), 1000), Some(periods), "{c:?}");
+        }
+        // Escapes straight away, so there's nothing to go on
+        assert_eq!(misiurewicz_periods(&big(2.0, 2.0, 2), 1000), None);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{BigFloat, FloatExp, Vec2};
+
+
+// Newton's method gets this many steps to settle before it's given up on
+pub const NEWTON_MAX_STEPS: u32 = 64;
+// Every Newton step runs the whole orbit at full precision on the event loop, so longer ones than this get turned down
+// rather than freezing the window
+pub const MAX_NEWTON_ORBIT: u32 = 1024;
+// Minibrots get zoomed to this many of their sizes across the pane's half height, which fits the whole antenna in
+pub const MINIBROT_FRAMING: f64 = 2.5;
+
+// The center of a minibrot or bulb, where 0 is periodic with the period
+#[derive(Debug, Clone, PartialEq)]
+pub struct Nucleus {
+    pub c: Vec2<BigFloat>,
+    pub period: u32,
+    // Roughly how big the minibrot is next to the whole set
+    pub size: FloatExp,
+}
+
+// Deep zoom values need the extra exponent, but not the extra precision, so these stay in FloatExp
+fn complex_mul(a: Vec2<FloatExp>, b: Vec2<FloatExp>) -> Vec2<FloatExp> {
+    Vec2(a.0 * b.0 - a.1 * b.1, a.0 * b.1 + a.1 * b.0)
+}
+
+pub(crate) fn complex_div(a: Vec2<FloatExp>, b: Vec2<FloatExp>) -> Vec2<FloatExp> {
+    let norm = norm_squared(b);
+    Vec2((a.0 * b.0 + a.1 * b.1) / norm, (a.1 * b.0 - a.0 * b.1) / norm)
+}
+
+pub(crate) fn norm_squared(z: Vec2<FloatExp>) -> FloatExp {
+    z.0 * z.0 + z.1 * z.1
+}
+
+pub(crate) fn to_float_exp(z: &Vec2<BigFloat>) -> Vec2<FloatExp> {
+    Vec2(z.0.to_float_exp(), z.1.to_float_exp())
+}
+
+// a - b, which only needs the extra exponent once the two have been taken apart at full precision
+pub(crate) fn big_difference(a: &Vec2<BigFloat>, b: &Vec2<BigFloat>) -> Vec2<FloatExp> {
+    Vec2((&a.0 - &b.0).to_float_exp(), (&a.1 - &b.1).to_float_exp())
+}
+
+// z^2 + c
+pub(crate) fn big_step(z: &Vec2<BigFloat>, c: &Vec2<BigFloat>) -> Vec2<BigFloat> {
+    let re_im = &z.0 * &z.1;
+    Vec2(&(&z.0.square() - &z.1.square()) + &c.0, &(&re_im + &re_im) + &c.1)
+}
+
+// The next dz/dc for z^2 + c, given the z it was the derivative of
+pub(crate) fn derivative_step(z: Vec2<FloatExp>, dc: Vec2<FloatExp>) -> Vec2<FloatExp> {
+    let product = complex_mul(z, dc);
+    Vec2(product.0 * 2.0 + FloatExp::ONE, product.1 * 2.0)
+}
+
+// Stepping c by the FloatExp correction, at however many limbs the answer's being worked out to
+pub(crate) fn newton_update(c: &Vec2<BigFloat>, delta: Vec2<FloatExp>, limbs: usize) -> Vec2<BigFloat> {
+    Vec2(&c.0 - &BigFloat::from_float_exp(delta.0, limbs), &c.1 - &BigFloat::from_float_exp(delta.1, limbs))
+}
+
+// Small enough for a Newton step to count as done at this many limbs. The last few bits are all rounding anyway.
+pub(crate) fn newton_tolerance(limbs: usize) -> FloatExp {
+    FloatExp::new(1.0, 16 - 32 * limbs as i32)
+}
+
+// The atom domain c is in: the iteration where the orbit got closest to 0 before it escaped. Around a minibrot that's the
+// minibrot's period, since that's when every orbit near it comes back around close to 0.
+pub fn atom_period(c: &Vec2<BigFloat>, max_iterations: u32) -> u32 {
+    let limbs = c.0.fraction_limbs().max(c.1.fraction_limbs());
+    let escape = FloatExp::from_f64(4.0);
+    let mut z = Vec2(BigFloat::zero(limbs), BigFloat::zero(limbs));
+    let mut closest = None;
+    let mut period = 1;
+    for n in 1..=max_iterations {
+        z = big_step(&z, c);
+        let norm = norm_squared(to_float_exp(&z));
+        if norm > escape { break }
+        if closest.is_none_or(|closest| norm < closest) {
+            closest = Some(norm);
+            period = n;
+        }
+    }
+    period
+}
+
+// Solves z_period(c) = 0 with Newton's method, starting from guess. z runs at full precision, while the derivative only
+// needs to be close for the steps to keep converging, so it gets away with FloatExp. If the minibrot turns out to be too
+// small for limbs to place it, it goes again with more.
+pub fn find_nucleus(guess: &Vec2<BigFloat>, period: u32, limbs: usize) -> crate::Result<Nucleus> {
+    if period == 0 { return Err("the period has to be at least 1".into()) }
+    if period > MAX_NEWTON_ORBIT { return Err(format!("period {period} is too long to solve for, the most is {MAX_NEWTON_ORBIT}").into()) }
+    let zero = Vec2(BigFloat::zero(limbs), BigFloat::zero(limbs));
+    let tolerance = newton_tolerance(limbs);
+    let mut c = Vec2(guess.0.with_precision(limbs), guess.1.with_precision(limbs));
+    let mut converged = false;
+    for _ in 0..NEWTON_MAX_STEPS {
+        let mut z = zero.clone();
+        let mut dc = Vec2(FloatExp::ZERO, FloatExp::ZERO);
+        for _ in 0..period {
+            dc = derivative_step(to_float_exp(&z), dc);
+            z = big_step(&z, &c);
+        }
+        if norm_squared(dc).is_zero() { return Err("Newton's method landed on a critical point".into()) }
+        
+        let delta = complex_div(to_float_exp(&z), dc);
+        c = newton_update(&c, delta, limbs);
+        if norm_squared(to_float_exp(&c)) > FloatExp::from_f64(4.0) { return Err(format!("Newton's method ran off the set looking for period {period}").into()) }
+        if norm_squared(delta) < tolerance * tolerance {
+            converged = true;
+            break
+        }
+    }
+    if !converged { return Err(format!("Newton's method didn't settle on a period {period} nucleus").into()) }
+    
+    // A nucleus is a root for every multiple of its period too, and inside a bulb the atom domain tends to pick one
+    let mut z = zero;
+    let mut dc = Vec2(FloatExp::ZERO, FloatExp::ZERO);
+    let threshold = tolerance * FloatExp::new(1.0, 16);
+    let mut true_period = period;
+    for n in 1..period {
+        dc = derivative_step(to_float_exp(&z), dc);
+        z = big_step(&z, &c);
+        if period.is_multiple_of(n) && norm_squared(to_float_exp(&z)) < norm_squared(dc) * threshold * threshold {
+            true_period = n;
+            break
+        }
+    }
+    
+    let size = minibrot_size(&c, true_period);
+    let needed = BigFloat::limbs_for_scale(size);
+    if needed > limbs { return find_nucleus(&c, true_period, needed) }
+    Ok(Nucleus { c, period: true_period, size })
+}
+
+// The usual size estimate, from the derivatives around the cycle: the minibrot is a copy of the whole set scaled down
+// by about 1 / (b·λ²), where λ is the cycle's derivative without its last step.
+pub fn minibrot_size(c: &Vec2<BigFloat>, period: u32) -> FloatExp {
+    let limbs = c.0.fraction_limbs().max(c.1.fraction_limbs());
+    let one = Vec2(FloatExp::ONE, FloatExp::ZERO);
+    let mut z = Vec2(BigFloat::zero(limbs), BigFloat::zero(limbs));
+    let mut l = one;
+    let mut b = one;
+    for _ in 1..period {
+        z = big_step(&z, c);
+        let two_z = to_float_exp(&z);
+        l = complex_mul(Vec2(two_z.0 * 2.0, two_z.1 * 2.0), l);
+        let inverse = complex_div(one, l);
+        b = Vec2(b.0 + inverse.0, b.1 + inverse.1);
+    }
+    FloatExp::ONE / (norm_squared(b).sqrt() * norm_squared(l))
+}
+
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    
+    pub(crate) fn big(re: f64, im: f64, limbs: usize) -> Vec2<BigFloat> {
+        Vec2(BigFloat::from_f64(re, limbs), BigFloat::from_f64(im, limbs))
+    }
+    
+    // z_0 through z_steps for c, at c's precision
+    pub(crate) fn orbit(c: &Vec2<BigFloat>, steps: usize) -> Vec<Vec2<BigFloat>> {
+        let limbs = c.0.fraction_limbs().max(c.1.fraction_limbs());
+        let mut orbit = vec![Vec2(BigFloat::zero(limbs), BigFloat::zero(limbs))];
+        for n in 0..steps { orbit.push(big_step(&orbit[n], c)) }
+        orbit
+    }
+    
+    // Zero to all but the last limb, which is all rounding by the time Newton's method has settled
+    pub(crate) fn assert_zero_to_precision(z: Vec2<FloatExp>, limbs: usize) {
+        assert!(norm_squared(z).log2() < -2.0 * 32.0 * (limbs - 1) as f64, "{z:?}");
+    }
+    
+    #[test]
+    fn nuclei_come_from_nearby_points() {
+        // Inside the period 3 bulb and just outside the real period 3 minibrot
+        for (guess, nucleus) in [((-0.12, 0.75), (-0.12256116687665362, 0.7448617666197442)), ((-1.7549, 0.0005), (-1.7548776662466927, 0.0))] {
+            let guess = big(guess.0, guess.1, 2);
+            let found = find_nucleus(&guess, atom_period(&guess, 1000), 2).unwrap();
+            assert_eq!(found.period, 3);
+            assert!((found.c.0.to_f64() - nucleus.0).abs() < 1e-15 && (found.c.1.to_f64() - nucleus.1).abs() < 1e-15, "{:?}", found.c);
+        }
+    }
+    
+    #[test]
+    fn nuclei_are_exact_to_the_precision() {
+        let limbs = 8;
+        let found = find_nucleus(&big(-1.75, 0.0, limbs), 3, limbs).unwrap();
+        assert_zero_to_precision(to_float_exp(&orbit(&found.c, 3)[3]), limbs);
+        // The real period 3 minibrot is about a fiftieth the size of the whole set
+        assert!((found.size.to_f64() - 0.019).abs() < 0.002, "{:e}", found.size);
+        assert_eq!(minibrot_size(&big(0.0, 0.0, 2), 1), FloatExp::ONE);
+    }
+    
+    #[test]
+    fn tiny_minibrots_get_more_precision() {
+        // The period 20 minibrot closest to -2, which is about 1e-22 across and needs more than 2 limbs to place
+        let found = find_nucleus(&big(-2.0, 0.0, 2), 20, 2).unwrap();
+        assert_eq!(found.period, 20);
+        assert!(found.size < FloatExp::from_f64(1e-20), "{:e}", found.size);
+        assert!(found.c.0.fraction_limbs() > 2);
+        assert!(found.c.0 > BigFloat::from_f64(-2.0, 2));
+        assert!(find_nucleus(&big(-2.0, 0.0, 2), MAX_NEWTON_ORBIT + 1, 2).is_err());
+    }
+}
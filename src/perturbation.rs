@@ -0,0 +1,284 @@
+use crate::{BigFloat, FloatExp, FractalParams, Vec2};
+
+
+// The orbit of one point in the view, computed on the cpu at full precision. Every pixel then only has to iterate its
+// small difference from this orbit, which fits in an f32 long after the pixel's own coordinates stop fitting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReferenceOrbit {
+    // c for the mandelbrot set or the starting z for a julia set
+    pub point: Vec2<BigFloat>,
+    pub julia: Option<Vec2<f64>>,
+    pub max_iterations: u32,
+    pub bailout: f64,
+    // Z_0 through the first escaped iterate, or max_iterations + 1 values if it never escaped
+    pub points: Vec<Vec2<f64>>,
+}
+
+impl ReferenceOrbit {
+    // Iterates at the point's own precision, which is however much the view needed to get there
+    pub fn compute(point: Vec2<BigFloat>, params: &FractalParams) -> Self {
+        let limbs = point.0.fraction_limbs().max(point.1.fraction_limbs());
+        let (mut z, c) = match params.julia {
+            Some(c) => (point.clone(), Vec2(BigFloat::from_f64(c.0, limbs), BigFloat::from_f64(c.1, limbs))),
+            None => (Vec2(BigFloat::zero(limbs), BigFloat::zero(limbs)), point.clone()),
+        };
+        
+        let bailout_squared = params.bailout * params.bailout;
+        let mut points = Vec::with_capacity(params.max_iterations as usize + 1);
+        let mut z_f64 = Vec2(z.0.to_f64(), z.1.to_f64());
+        points.push(z_f64);
+        for _ in 0..params.max_iterations {
+            if z_f64.0 * z_f64.0 + z_f64.1 * z_f64.1 > bailout_squared { break }
+            let re_im = &z.0 * &z.1;
+            z = Vec2(&(&z.0.square() - &z.1.square()) + &c.0, &(&re_im + &re_im) + &c.1);
+            z_f64 = Vec2(z.0.to_f64(), z.1.to_f64());
+            points.push(z_f64);
+        }
+        
+        Self { point, julia: params.julia, max_iterations: params.max_iterations, bailout: params.bailout, points }
+    }
+    
+    // Whether this orbit can be reused for a view without recomputing it
+    pub fn matches(&self, point: &Vec2<BigFloat>, params: &FractalParams) -> bool {
+        self.point == *point && self.julia == params.julia && self.max_iterations == params.max_iterations && self.bailout == params.bailout
+    }
+    
+    pub fn gpu_points(&self) -> Vec<[f32; 2]> {
+        self.points.iter().map(|z| [z.0 as f32, z.1 as f32]).collect()
+    }
+}
+
+
+// How small the terms a bla drops have to be next to the ones it keeps. About an f32's precision, since that's what the
+// shader applies them in.
+pub const BLA_EPSILON: f64 = 1.0 / (1u32 << 24) as f64;
+
+// Bilinear approximation of a run of iterations: for any |dz| < radius, dz after the run is a·dz + b·dc. The radius
+// shrinks along with the view, so past f64's range it needs the extra exponent.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Bla {
+    pub a: Vec2<f64>,
+    pub b: Vec2<f64>,
+    pub radius: FloatExp,
+}
+
+impl Bla {
+    // One step of dz -> 2Z·dz + dz^2 + dc, which is linear as long as dz^2 is tiny next to 2Z·dz
+    fn step(z: Vec2<f64>) -> Self {
+        Self { a: Vec2(2.0 * z.0, 2.0 * z.1), b: Vec2(1.0, 0.0), radius: FloatExp::from_f64(BLA_EPSILON * length(z)) }
+    }
+    
+    // self then next. The second half is only valid if the first half lands dz inside its radius.
+    fn then(&self, next: &Self, max_dc: FloatExp) -> Self {
+        let b = complex_mul(next.a, self.b);
+        Self {
+            a: complex_mul(next.a, self.a),
+            b: Vec2(b.0 + next.b.0, b.1 + next.b.1),
+            radius: self.radius.min(((next.radius - max_dc * length(self.b)) / FloatExp::from_f64(length(self.a))).max(FloatExp::ZERO)),
+        }
+    }
+}
+
+// How a bla goes into the shader's table
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GpuBla {
+    pub a: [f32; 2],
+    pub b: [f32; 2],
+    pub radius_mantissa: f32,
+    pub radius_exponent: i32,
+}
+
+fn complex_mul(a: Vec2<f64>, b: Vec2<f64>) -> Vec2<f64> {
+    Vec2(a.0 * b.0 - a.1 * b.1, a.0 * b.1 + a.1 * b.0)
+}
+
+fn length(z: Vec2<f64>) -> f64 {
+    z.0.hypot(z.1)
+}
+
+// Blas for every power of two run of the reference orbit, so a pixel can skip most of the iterations where its dz
+// stays small
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlaTable {
+    // Mandelbrot orbits start at 0, where dz is 0 for every pixel and nothing can be skipped, so their tables start a
+    // step later once dz = dc
+    pub first: u32,
+    // levels[k][j] covers 2^k iterations starting at first + j·2^k
+    pub levels: Vec<Vec<Bla>>,
+    // The biggest |dc| of any pixel the table has to work for
+    pub max_dc: FloatExp,
+}
+
+impl BlaTable {
+    pub fn compute(orbit: &ReferenceOrbit, max_dc: FloatExp) -> Self {
+        let first = if orbit.julia.is_some() { 0 } else { 1 };
+        // Julia sets have no dc at all
+        let effective_dc = if orbit.julia.is_some() { FloatExp::ZERO } else { max_dc };
+        // Each step needs the iterate after it too, so the escaped point at the end doesn't start one
+        let steps = orbit.points.len().saturating_sub(first + 1);
+        
+        let mut levels = vec![orbit.points[first..first + steps].iter().map(|&z| Bla::step(z)).collect::<Vec<_>>()];
+        while levels.len() < 32 && levels.last().unwrap().len() >= 2 {
+            let level = levels.last().unwrap().chunks_exact(2).map(|pair| pair[0].then(&pair[1], effective_dc)).collect();
+            levels.push(level);
+        }
+        
+        Self { first: first as u32, levels, max_dc }
+    }
+    
+    // Entries that overflow an f32 get a radius of 0 so they never get used
+    pub fn gpu_entries(&self) -> Vec<GpuBla> {
+        self.levels.iter().flatten().map(|bla| {
+            let [a0, a1, b0, b1] = [bla.a.0, bla.a.1, bla.b.0, bla.b.1].map(|x| x as f32);
+            let radius = if [a0, a1, b0, b1].iter().all(|x| x.is_finite()) { bla.radius } else { FloatExp::ZERO };
+            GpuBla { a: [a0, a1], b: [b0, b1], radius_mantissa: radius.mantissa as f32, radius_exponent: radius.exponent }
+        }).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn big(re: f64, im: f64) -> Vec2<BigFloat> {
+        Vec2(BigFloat::from_f64(re, 2), BigFloat::from_f64(im, 2))
+    }
+    
+    fn step(z: Vec2<f64>, c: Vec2<f64>) -> Vec2<f64> {
+        Vec2(z.0 * z.0 - z.1 * z.1 + c.0, 2.0 * z.0 * z.1 + c.1)
+    }
+    
+    fn add(a: Vec2<f64>, b: Vec2<f64>) -> Vec2<f64> {
+        Vec2(a.0 + b.0, a.1 + b.1)
+    }
+    
+    fn apply(bla: &Bla, dz: Vec2<f64>, dc: Vec2<f64>) -> Vec2<f64> {
+        add(complex_mul(bla.a, dz), complex_mul(bla.b, dc))
+    }
+    
+    // One full perturbation step: the length 1 bla the table is built from, plus the dz^2 it leaves out
+    fn perturb(orbit: &ReferenceOrbit, n: usize, dz: Vec2<f64>, dc: Vec2<f64>) -> Vec2<f64> {
+        add(apply(&Bla::step(orbit.points[n]), dz, dc), complex_mul(dz, dz))
+    }
+    
+    #[test]
+    fn reference_orbits_match_f64_when_shallow() {
+        // Inside the period 3 bulb, where f64's rounding errors die out instead of growing
+        let params = FractalParams { max_iterations: 200, ..Default::default() };
+        let c = Vec2(-0.12, 0.75);
+        let orbit = ReferenceOrbit::compute(big(c.0, c.1), &params);
+        assert_eq!(orbit.points.len(), 201);
+        let mut z = Vec2(0.0, 0.0);
+        for point in &orbit.points {
+            assert!((point.0 - z.0).abs() < 1e-12 && (point.1 - z.1).abs() < 1e-12, "{point:?} vs {z:?}");
+            z = step(z, c);
+        }
+    }
+    
+    #[test]
+    fn perturbed_pixels_match_direct_iteration() {
+        let params = FractalParams { max_iterations: 100, ..Default::default() };
+        let (reference, dc) = (Vec2(-0.12, 0.75), Vec2(1e-9, -2e-9));
+        let c = Vec2(reference.0 + dc.0, reference.1 + dc.1);
+        let orbit = ReferenceOrbit::compute(big(reference.0, reference.1), &params);
+        
+        // dz -> 2Z·dz + dz^2 + dc, same as iterate_perturbation() in perturbation.wgsl takes when there's no skip
+        let (mut z, mut dz) = (Vec2(0.0, 0.0), Vec2(0.0, 0.0));
+        for (n, point) in orbit.points[..params.max_iterations as usize].iter().enumerate() {
+            assert!((point.0 + dz.0 - z.0).abs() < 1e-12 && (point.1 + dz.1 - z.1).abs() < 1e-12);
+            dz = perturb(&orbit, n, dz, dc);
+            z = step(z, c);
+        }
+    }
+    
+    #[test]
+    fn escaping_references_stop_at_the_escape() {
+        let params = FractalParams { max_iterations: 100, ..Default::default() };
+        // 0, 1, 2, 5, and 5 is out
+        let orbit = ReferenceOrbit::compute(big(1.0, 0.0), &params);
+        assert_eq!(orbit.points, [Vec2(0.0, 0.0), Vec2(1.0, 0.0), Vec2(2.0, 0.0), Vec2(5.0, 0.0)]);
+        assert_eq!(orbit.gpu_points().len(), 4);
+        // Never escaping gets every iteration
+        assert_eq!(ReferenceOrbit::compute(big(0.0, 0.0), &params).points.len(), 101);
+    }
+    
+    // Same search as find_bla() in perturbation.wgsl: the biggest level n is aligned to whose radius dz fits in
+    fn find_bla(table: &BlaTable, n: usize, dz: Vec2<f64>) -> Option<(&Bla, usize)> {
+        let m = n.checked_sub(table.first as usize)?;
+        if table.levels.is_empty() { return None }
+        let len = table.levels[0].len();
+        (1..=(m.trailing_zeros() as usize).min(table.levels.len() - 1)).rev().find_map(|level| {
+            let index = m >> level;
+            if index >= len >> level { return None }
+            let bla = &table.levels[level][index];
+            (FloatExp::from_f64(length(dz)) < bla.radius).then_some((bla, 1 << level))
+        })
+    }
+    
+    // Inside the period 3 bulb, so the reference runs the full length
+    fn interior_table(max_dc: f64) -> (ReferenceOrbit, BlaTable) {
+        let params = FractalParams { max_iterations: 256, ..Default::default() };
+        let orbit = ReferenceOrbit::compute(big(-0.12, 0.75), &params);
+        let table = BlaTable::compute(&orbit, FloatExp::from_f64(max_dc));
+        (orbit, table)
+    }
+    
+    #[test]
+    fn bla_skips_match_stepping_one_at_a_time() {
+        let max_dc = 1e-10;
+        let (orbit, table) = interior_table(max_dc);
+        let dc = Vec2(3e-11, -5e-11);
+        
+        // dz_1 = dc for every mandelbrot pixel, which is where the table starts
+        let (mut n, mut dz) = (1, dc);
+        let (mut exact_n, mut exact) = (1, dc);
+        let mut skipped = 0;
+        while n + 1 < orbit.points.len() {
+            match find_bla(&table, n, dz) {
+                Some((bla, length)) => {
+                    dz = apply(bla, dz, dc);
+                    n += length;
+                    skipped += length;
+                }
+                None => {
+                    dz = perturb(&orbit, n, dz, dc);
+                    n += 1;
+                }
+            }
+            while exact_n < n {
+                exact = perturb(&orbit, exact_n, exact, dc);
+                exact_n += 1;
+            }
+            assert!(length(Vec2(dz.0 - exact.0, dz.1 - exact.1)) <= 1e-5 * length(exact), "{dz:?} vs {exact:?} at {n}");
+        }
+        assert!(skipped > orbit.points.len() / 2, "only skipped {skipped}");
+    }
+    
+    #[test]
+    fn bla_radii_are_conservative() {
+        let max_dc = 1e-10;
+        let (orbit, table) = interior_table(max_dc);
+        for (level, entries) in table.levels.iter().enumerate() {
+            let length_per_entry = 1 << level;
+            // Every run ends inside the orbit, the last one of each level included
+            assert_eq!(entries.len(), table.levels[0].len() >> level);
+            assert!(table.first as usize + entries.len() * length_per_entry < orbit.points.len());
+            
+            // Right up against the radius and with the biggest dc, the dropped terms still barely matter
+            for (index, bla) in entries.iter().enumerate().filter(|&(index, _)| level <= 4 || index + 1 == entries.len()) {
+                let radius = 0.99 * bla.radius.to_f64();
+                for angle in [0.0, 2.0, 4.0] {
+                    let (dz, dc) = (Vec2(radius * f64::cos(angle), radius * f64::sin(angle)), Vec2(0.0, max_dc));
+                    let start = table.first as usize + index * length_per_entry;
+                    let exact = (start..start + length_per_entry).fold(dz, |dz, n| perturb(&orbit, n, dz, dc));
+                    let approximate = apply(bla, dz, dc);
+                    let size = length(complex_mul(bla.a, dz)) + length(complex_mul(bla.b, dc));
+                    let error = length(Vec2(approximate.0 - exact.0, approximate.1 - exact.1));
+                    assert!(error <= 4.0 * length_per_entry as f64 * BLA_EPSILON * size, "level {level} entry {index}: {error:e} of {size:e}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,227 @@
+// Perturbation rendering, kept out of shader.wgsl since webgl can't have storage buffers. Each pixel iterates only its
+// difference dz from the reference orbit Z: (Z + dz)^2 + c = Z^2 + C + (2Z + dz)dz + dc
+//
+// There's no interior detection down here. Deep views are all within a hair of the boundary, and Z + dz as an f32 is
+// nowhere near precise enough to tell a pixel's orbit cycling from one that's just passing close to where it was.
+
+struct ReferenceOrbit {
+    // Where the orbit starts relative to center, as a floatexp
+    offset: vec2<f32>,
+    offset_exponent: i32,
+    len: u32,
+    points: array<vec2<f32>>,
+};
+
+// Bilinear approximations of power of two runs of the reference orbit: for |dz| < r, dz after the run is a·dz + b·dc
+struct Bla {
+    a: vec2<f32>,
+    b: vec2<f32>,
+    // A floatexp, since the radius gets as small as the view does
+    r: f32,
+    r_exponent: i32,
+};
+
+struct BlaTable {
+    // The iteration level 0 starts at, and how many entries it has. Level k has len >> k.
+    first: u32,
+    len: u32,
+    levels: u32,
+    level_starts: array<u32, 32>,
+    entries: array<Bla>,
+};
+
+@group(2) @binding(0)
+var<storage, read> reference: ReferenceOrbit;
+@group(2) @binding(1)
+var<storage, read> bla: BlaTable;
+
+// One flag per surface pixel, set wherever the reference orbit was no good for that pixel
+@group(3) @binding(0)
+var<storage, read_write> glitches: array<u32>;
+
+// Pauldelbrot's criterion: once |Z + dz| gets this much smaller than |Z|, dz has lost too many bits relative to the
+// pixel's actual value and the rest of the iteration is garbage
+const GLITCH_TOLERANCE: f32 = 1e-3;
+
+struct PerturbationResult {
+    escape: Escape,
+    glitched: bool,
+};
+
+struct BlaStep {
+    bla: Bla,
+    steps: u32,
+};
+
+// The longest run starting at iteration n that's still accurate for this dz, or 0 steps if there isn't one worth taking.
+// Runs of 2^k only start at multiples of 2^k past the table's first iteration.
+fn find_bla(n: u32, dz: ComplexExp) -> BlaStep {
+    let none = BlaStep(Bla(vec2<f32>(0.0), vec2<f32>(0.0), 0.0, 0), 0u);
+    if n < bla.first || bla.levels == 0u { return none; }
+    let m = n - bla.first;
+    for (var level = min(countTrailingZeros(m), bla.levels - 1u); level >= 1u; level--) {
+        let index = m >> level;
+        if index >= bla.len >> level { continue; }
+        let entry = bla.entries[bla.level_starts[level] + index];
+        if cx_smaller_than(dz, entry.r, entry.r_exponent) { return BlaStep(entry, 1u << level); }
+    }
+    return none;
+}
+
+fn reference_offset() -> ComplexExp {
+    return ComplexExp(reference.offset, reference.offset_exponent);
+}
+
+fn iterate_perturbation(offset: vec2<f32>) -> PerturbationResult {
+    let delta = offset - cx_to_f32(reference_offset());
+    var dz = vec2<f32>(0.0, 0.0);
+    var dc = delta;
+    if u.mode == MODE_JULIA {
+        dz = delta;
+        dc = vec2<f32>(0.0, 0.0);
+    }
+
+    var derivative = derivative_start();
+    var orbit = orbit_start();
+    var n = 0u;
+    for (; n < u.max_iter; n++) {
+        if use_bla() {
+            let skip = find_bla(n, cx_from_f32(dz));
+            if skip.steps > 0u {
+                dz = complex_mul(skip.bla.a, dz) + complex_mul(skip.bla.b, dc);
+                n += skip.steps - 1u;
+                continue;
+            }
+        }
+
+        let reference_z = reference.points[n];
+        let z = reference_z + dz;
+        if n > 0u { orbit = orbit_update(orbit, z); }
+        if dot(z, z) > u.bailout * u.bailout { return PerturbationResult(Escape(n, z, distance_estimate(z, derivative), 0u, false, orbit), false); }
+        if dot(z, z) < GLITCH_TOLERANCE * GLITCH_TOLERANCE * dot(reference_z, reference_z) {
+            return PerturbationResult(Escape(n, z, 0.0, 0u, false, orbit), true);
+        }
+
+        // The reference escaped before this pixel did, so finish off with the full value. It's only a few iterations
+        // from escaping at that point so the lost precision doesn't matter much.
+        if n + 1u >= reference.len {
+            var c = u.center + offset;
+            if u.mode == MODE_JULIA { c = u.julia_c; }
+            return PerturbationResult(iterate_from(z, derivative, c, n, orbit), false);
+        }
+
+        derivative = derivative_step(z, derivative);
+        dz = complex_mul(2.0 * reference_z + dz, dz) + dc;
+    }
+    return PerturbationResult(Escape(n, last_z(n, dz), 0.0, 0u, false, orbit), false);
+}
+
+// Skipping iterations skips the derivative along with them, so distance estimates have to take the long way, and so
+// do orbit traps and atom domains, which need to see every iterate
+fn use_bla() -> bool {
+    return u.use_bla != 0u && u.color_mode != COLOR_DISTANCE && u.trap_shape == TRAP_NONE && u.interior_mode != INTERIOR_ATOM_DOMAIN;
+}
+
+// Where a point that never escaped ended up, for interior coloring. Perturbation doesn't look for cycles, so the
+// interior modes that need one stay black down here.
+fn last_z(n: u32, dz: vec2<f32>) -> vec2<f32> {
+    return reference.points[min(n, reference.len - 1u)] + dz;
+}
+
+// Same thing with dz and dc as floatexps, for once they're too small for f32. Z + dz is still fine as an f32 since Z
+// is the bigger one by far.
+fn iterate_perturbation_floatexp(offset: ComplexExp) -> PerturbationResult {
+    let delta = cx_sub(offset, reference_offset());
+    var dz = cx_from_f32(vec2<f32>(0.0, 0.0));
+    var dc = delta;
+    if u.mode == MODE_JULIA {
+        dz = delta;
+        dc = cx_from_f32(vec2<f32>(0.0, 0.0));
+    }
+
+    // The pixel size is too small for an f32 down here, so the derivative is a floatexp too
+    let pixel_size = cx_normalize(vec2<f32>(2.0 * u.scale_mantissa / u.viewport_height, 0.0), u.scale_exponent);
+    var derivative = cx_from_f32(vec2<f32>(0.0));
+    if u.mode == MODE_JULIA { derivative = pixel_size; }
+    var orbit = orbit_start();
+    var n = 0u;
+    for (; n < u.max_iter; n++) {
+        if use_bla() {
+            let skip = find_bla(n, dz);
+            if skip.steps > 0u {
+                dz = cx_add(cx_mul_f32(dz, skip.bla.a), cx_mul_f32(dc, skip.bla.b));
+                n += skip.steps - 1u;
+                continue;
+            }
+        }
+
+        let reference_z = reference.points[n];
+        let z = reference_z + cx_to_f32(dz);
+        if n > 0u { orbit = orbit_update(orbit, z); }
+        if dot(z, z) > u.bailout * u.bailout { return PerturbationResult(Escape(n, z, cx_distance_estimate(z, derivative), 0u, false, orbit), false); }
+        if dot(z, z) < GLITCH_TOLERANCE * GLITCH_TOLERANCE * dot(reference_z, reference_z) {
+            return PerturbationResult(Escape(n, z, 0.0, 0u, false, orbit), true);
+        }
+
+        // The derivative has had long enough to grow back into f32's range by the time the reference runs out
+        if n + 1u >= reference.len {
+            var c = u.center;
+            if u.mode == MODE_JULIA { c = u.julia_c; }
+            return PerturbationResult(iterate_from(z, cx_to_f32(derivative), c, n, orbit), false);
+        }
+
+        derivative = cx_mul_f32(derivative, 2.0 * z);
+        if u.mode == MODE_MANDELBROT { derivative = cx_add(derivative, pixel_size); }
+        dz = cx_add(cx_mul(cx_add(cx_from_f32(2.0 * reference_z), dz), dz), dc);
+    }
+    return PerturbationResult(Escape(n, last_z(n, cx_to_f32(dz)), 0.0, 0u, false, orbit), false);
+}
+
+fn cx_distance_estimate(z: vec2<f32>, derivative: ComplexExp) -> f32 {
+    return ldexp(length(z) * log(length(z)) / length(derivative.m), clamp(-derivative.e, -126, 127));
+}
+
+fn pixel_offset_floatexp(in: VertexOutput) -> ComplexExp {
+    return cx_normalize(in.uv * vec2<f32>(u.aspect, 1.0) * u.scale_mantissa, u.scale_exponent);
+}
+
+fn perturbation_result(in: VertexOutput) -> PerturbationResult {
+    if u.use_floatexp != 0u { return iterate_perturbation_floatexp(pixel_offset_floatexp(in)); }
+    return iterate_perturbation(pixel_offset(in));
+}
+
+fn iterate_from(start: vec2<f32>, start_derivative: vec2<f32>, c: vec2<f32>, start_n: u32, start_orbit: Orbit) -> Escape {
+    var z = start;
+    var derivative = start_derivative;
+    var orbit = start_orbit;
+    var n = start_n;
+    for (; n < u.max_iter; n++) {
+        if dot(z, z) > u.bailout * u.bailout { break; }
+        derivative = derivative_step(z, derivative);
+        z = complex_mul(z, z) + c;
+        orbit = orbit_update(orbit, z);
+    }
+    return Escape(n, z, distance_estimate(z, derivative), 0u, false, orbit);
+}
+
+fn glitch_index(in: VertexOutput) -> u32 {
+    return u32(in.clip_position.y) * u.surface_width + u32(in.clip_position.x);
+}
+
+@fragment
+fn fs_perturbation(in: VertexOutput) -> @location(0) vec4<f32> {
+    let result = perturbation_result(in);
+    glitches[glitch_index(in)] = u32(result.glitched);
+    return shade(result.escape);
+}
+
+// Goes back over only the pixels the first pass flagged, this time against a secondary reference orbit from inside
+// the glitch. Anything still glitched stays flagged.
+@fragment
+fn fs_perturbation_fixup(in: VertexOutput) -> @location(0) vec4<f32> {
+    let index = glitch_index(in);
+    if glitches[index] == 0u { discard; }
+    let result = perturbation_result(in);
+    glitches[index] = u32(result.glitched);
+    return shade(result.escape);
+}
@@ -1,24 +1,143 @@
 
+struct Uniforms {
+    center: vec2<f32>,
+    scale: f32,
+    aspect: f32,
+    julia_c: vec2<f32>,
+    max_iter: u32,
+    mode: u32,
+    // Low halves of center and julia_c for double-float precision
+    center_lo: vec2<f32>,
+    julia_c_lo: vec2<f32>,
+    precision_mode: u32,
+    // For indexing per pixel buffers by the fragment's position
+    surface_width: u32,
+    // Whether perturbation can skip iterations with the bla table
+    use_bla: u32,
+    // Past f32's range perturbation switches to floatexp deltas, and the scale comes in as a mantissa and exponent
+    use_floatexp: u32,
+    scale_mantissa: f32,
+    scale_exponent: i32,
+    color_mode: u32,
+    bailout: f32,
+    viewport_height: f32,
+    // How far out from the boundary distance estimate coloring fades in, in pixels
+    line_thickness: f32,
+    fractal_type: u32,
+    // d in z^d + c for multibrots
+    exponent: f32,
+    // Newton's method steps get multiplied by this
+    relaxation: vec2<f32>,
+    newton_degree: u32,
+    // Which of a and b each step of a lyapunov fractal uses, one bit per letter with B for 1
+    sequence: u32,
+    sequence_length: u32,
+    // Orbit traps light up orbits that come close to a shape, within about trap_width of it. trap_angle is in radians.
+    trap_shape: u32,
+    trap_center: vec2<f32>,
+    trap_radius: f32,
+    trap_angle: f32,
+    trap_width: f32,
+    // Width over height of the image for image traps, which is group 0's texture
+    trap_aspect: f32,
+    // How points that never escape get colored
+    interior_mode: u32,
+    // The newton polynomial's coefficients in xy, highest power first, and its roots in zw
+    newton: array<vec4<f32>, 9>,
+};
+
+const MODE_MANDELBROT: u32 = 0u;
+const MODE_JULIA: u32 = 1u;
+
+const PRECISION_F32: u32 = 0u;
+const PRECISION_DF64: u32 = 1u;
+const PRECISION_PERTURBATION: u32 = 2u;
+
+const COLOR_BANDED: u32 = 0u;
+const COLOR_SMOOTH: u32 = 1u;
+const COLOR_DISTANCE: u32 = 2u;
+
+const FRACTAL_MANDELBROT: u32 = 0u;
+const FRACTAL_MULTIBROT: u32 = 1u;
+const FRACTAL_BURNING_SHIP: u32 = 2u;
+const FRACTAL_TRICORN: u32 = 3u;
+const FRACTAL_CELTIC: u32 = 4u;
+const FRACTAL_BUFFALO: u32 = 5u;
+const FRACTAL_PERPENDICULAR: u32 = 6u;
+const FRACTAL_NEWTON: u32 = 7u;
+const FRACTAL_PHOENIX: u32 = 8u;
+const FRACTAL_MAGNET_1: u32 = 9u;
+const FRACTAL_MAGNET_2: u32 = 10u;
+const FRACTAL_NOVA: u32 = 11u;
+const FRACTAL_CUSTOM: u32 = 12u;
+const FRACTAL_LYAPUNOV: u32 = 13u;
+
+const TRAP_NONE: u32 = 0u;
+const TRAP_POINT: u32 = 1u;
+const TRAP_LINE: u32 = 2u;
+const TRAP_CIRCLE: u32 = 3u;
+const TRAP_CROSS: u32 = 4u;
+const TRAP_IMAGE: u32 = 5u;
+// How far from the trap an orbit counts as when it never got a chance to come near it
+const TRAP_FAR: f32 = 1e30;
+
+const INTERIOR_BLACK: u32 = 0u;
+const INTERIOR_MAGNITUDE: u32 = 1u;
+const INTERIOR_ANGLE: u32 = 2u;
+const INTERIOR_PERIOD: u32 = 3u;
+const INTERIOR_DISTANCE: u32 = 4u;
+const INTERIOR_ATOM_DOMAIN: u32 = 5u;
+// Newton steps to get from wherever cycle detection noticed the cycle to the cycle itself
+const INTERIOR_NEWTON_STEPS: u32 = 4u;
+
+// Orbits that settle down count as converged once their steps get this short, or once magnets get this close to 1
+const CONVERGENCE_TOLERANCE: f32 = 1e-4;
+// Magnet orbits wander well past 2 on their way to converging, and nova orbits hardly ever escape at all
+const MAGNET_BAILOUT: f32 = 100.0;
+const NOVA_BAILOUT: f32 = 1e10;
+// Iterations the logistic map gets to settle in before the exponent starts adding up
+const LYAPUNOV_WARMUP: u32 = 64u;
+// Keeps superstable points, where the derivative is exactly 0, from taking the log to -infinity
+const LYAPUNOV_FLOOR: f32 = 1e-30;
 
-struct VertexInput {
-    @location(0) position: vec3<f32>,
-    @location(1) normal: vec3<f32>,
+// How many iterations a point took to escape, max_iter if it never did, the z it escaped with, the exterior distance
+// estimate in pixels, and the period of the cycle it fell into if it got caught in one, 0 otherwise. Magnets and novas
+// can converge instead of escaping, which counts the same except for the flag. Points that got caught in a cycle get
+// the interior distance estimate instead, if that's what the interior is colored by.
+struct Escape {
+    n: u32,
+    z: vec2<f32>,
+    distance: f32,
+    period: u32,
+    converged: bool,
+    orbit: Orbit,
+};
+
+// What gets kept track of along the way for coloring: the closest the orbit came to the orbit trap, where in the
+// trap's image the first point to land on it did, or -1s if none did, and for atom domains which iterate set the last
+// record for getting close to 0
+struct Orbit {
+    trap: f32,
+    trap_image: vec2<f32>,
+    steps: u32,
+    closest: f32,
+    atom: u32,
 };
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
-    @location(0) normal: vec3<f32>,
+    @location(0) uv: vec2<f32>,
 };
 
 @group(1) @binding(0)
-var<uniform> mouse_position: vec4<f32>;
+var<uniform> u: Uniforms;
 
+// One triangle big enough to cover the whole screen, uv goes from -1 to 1 across the visible part
 @vertex
-fn vs_main(model: VertexInput) -> VertexOutput {
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
     var out: VertexOutput;
-    out.clip_position = vec4<f32>(model.position * vec3<f32>(1.0, 1.0, -1.0), 1.0);
-    out.clip_position = vec4<f32>(out.clip_position.xy * 0.2 + (mouse_position.xy * 2 - 1) * vec2<f32>(1, -1), out.clip_position.zw);
-    out.normal = model.normal * vec3<f32>(1.0, 1.0, -1.0);
+    out.uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u)) * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(out.uv, 0.0, 1.0);
     return out;
 }
 
@@ -28,8 +147,561 @@ var t: texture_2d<f32>;
 @group(0) @binding(1)
 var s: sampler;
 
+// Must match palette() in fractal.rs
+fn palette(t: f32) -> vec3<f32> {
+    return 0.5 + 0.5 * cos(6.2831853 * (t * 0.02 + vec3<f32>(0.0, 0.1, 0.2)));
+}
+
+// The surface is srgb, so colors that are meant to look like the cpu's rgba bytes have to be linearized first
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    return select(pow((color + 0.055) / 1.055, vec3<f32>(2.4)), color / 12.92, color <= vec3<f32>(0.04045));
+}
+
+fn complex_mul(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x);
+}
+
+// z^d, by repeated multiplication for whole powers and in polar form otherwise, or for ones too big to loop over. 0^d
+// comes out 0 even for negative d, so mandelbrot orbits still go 0, c, ... Must match complex_pow() in fractal.rs.
+fn complex_pow(z: vec2<f32>, d: f32) -> vec2<f32> {
+    if all(z == vec2<f32>(0.0)) { return vec2<f32>(0.0); }
+    if d != 0.0 && d == round(d) && abs(d) <= 64.0 {
+        var power = z;
+        for (var i = 1; i < i32(abs(d)); i++) { power = complex_mul(power, z); }
+        if d > 0.0 { return power; }
+        return vec2<f32>(power.x, -power.y) / dot(power, power);
+    }
+    let r = pow(length(z), d);
+    let theta = atan2(z.y, z.x) * d;
+    return r * vec2<f32>(cos(theta), sin(theta));
+}
+
+fn conjugate(z: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(z.x, -z.y);
+}
+
+fn complex_div(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    return complex_mul(a, conjugate(b)) / dot(b, b);
+}
+
+// The rest of the functions typed-in formulas can use. Must match the ones on Complex in math.rs.
+fn complex_arg(z: vec2<f32>) -> f32 {
+    return atan2(z.y, z.x);
+}
+
+fn complex_exp(z: vec2<f32>) -> vec2<f32> {
+    return exp(z.x) * vec2<f32>(cos(z.y), sin(z.y));
+}
+
+fn complex_log(z: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(log(length(z)), complex_arg(z));
+}
+
+fn complex_sqrt(z: vec2<f32>) -> vec2<f32> {
+    let theta = complex_arg(z) / 2.0;
+    return sqrt(length(z)) * vec2<f32>(cos(theta), sin(theta));
+}
+
+fn complex_powc(z: vec2<f32>, w: vec2<f32>) -> vec2<f32> {
+    if all(z == vec2<f32>(0.0)) { return vec2<f32>(0.0); }
+    return complex_exp(complex_mul(w, complex_log(z)));
+}
+
+fn complex_sin(z: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(sin(z.x) * cosh(z.y), cos(z.x) * sinh(z.y));
+}
+
+fn complex_cos(z: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(cos(z.x) * cosh(z.y), -sin(z.x) * sinh(z.y));
+}
+
+fn complex_tan(z: vec2<f32>) -> vec2<f32> {
+    return complex_div(complex_sin(z), complex_cos(z));
+}
+
+fn complex_sinh(z: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(sinh(z.x) * cos(z.y), cosh(z.x) * sin(z.y));
+}
+
+fn complex_cosh(z: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(cosh(z.x) * cos(z.y), sinh(z.x) * sin(z.y));
+}
+
+fn complex_tanh(z: vec2<f32>) -> vec2<f32> {
+    return complex_div(complex_sinh(z), complex_cosh(z));
+}
+
+// The relaxed Newton step for the polynomial, R·p(z)/p'(z)
+fn newton_step(z: vec2<f32>) -> vec2<f32> {
+    // Horner's method for p and p' at once
+    var p = vec2<f32>(0.0);
+    var derivative = vec2<f32>(0.0);
+    for (var i = 0u; i <= u.newton_degree; i++) {
+        derivative = complex_mul(derivative, z) + p;
+        p = complex_mul(p, z) + u.newton[i].xy;
+    }
+    return complex_mul(u.relaxation, complex_div(p, derivative));
+}
+
+// Newton's method on the polynomial, starting from the pixel. Escape.n is how many steps it took to converge, and z is
+// where it converged to. Must match FractalParams::sample_newton() in fractal.rs.
+fn iterate_newton(position: vec2<f32>) -> Escape {
+    var z = position;
+    var n = 0u;
+    for (; n < u.max_iter; n++) {
+        let step = newton_step(z);
+        z -= step;
+        if dot(step, step) < CONVERGENCE_TOLERANCE * CONVERGENCE_TOLERANCE { break; }
+    }
+    return Escape(n, z, 0.0, 0u, false, orbit_start());
+}
+
+// Which root the orbit ended up at, spread out around the palette. Must match Sample::value in fractal.rs.
+fn newton_value(z: vec2<f32>) -> f32 {
+    var nearest = 0u;
+    for (var i = 1u; i < u.newton_degree; i++) {
+        let offset = z - u.newton[i].zw;
+        let nearest_offset = z - u.newton[nearest].zw;
+        if dot(offset, offset) < dot(nearest_offset, nearest_offset) { nearest = i; }
+    }
+    return 50.0 * f32(nearest) / f32(u.newton_degree);
+}
+
+// Stands in for a typed-in formula, which Formula::splice() swaps this line out for when the pipeline gets built
+fn custom_step(z: vec2<f32>, c: vec2<f32>) -> vec2<f32> { return complex_mul(z, z) + c; }
+
+// One iteration of whichever fractal type is selected. Must match FractalParams::step() in fractal.rs.
+fn fractal_step(z: vec2<f32>, c: vec2<f32>) -> vec2<f32> {
+    switch u.fractal_type {
+        case FRACTAL_MULTIBROT: { return complex_pow(z, u.exponent) + c; }
+        case FRACTAL_BURNING_SHIP: { return complex_mul(abs(z), abs(z)) + c; }
+        case FRACTAL_TRICORN: { return conjugate(complex_mul(z, z)) + c; }
+        case FRACTAL_CELTIC: {
+            let square = complex_mul(z, z);
+            return vec2<f32>(abs(square.x), square.y) + c;
+        }
+        case FRACTAL_BUFFALO: {
+            let square = complex_mul(z, z);
+            return vec2<f32>(abs(square.x), -abs(square.y)) + c;
+        }
+        case FRACTAL_PERPENDICULAR: {
+            let folded = vec2<f32>(abs(z.x), z.y);
+            return conjugate(complex_mul(folded, folded)) + c;
+        }
+        // The previous z gets added on separately. c's imaginary part is its coefficient, so only the real part goes here.
+        case FRACTAL_PHOENIX: { return complex_mul(z, z) + vec2<f32>(c.x, 0.0); }
+        case FRACTAL_MAGNET_1: {
+            let one = vec2<f32>(1.0, 0.0);
+            let ratio = complex_div(complex_mul(z, z) + c - one, 2.0 * z + c - 2.0 * one);
+            return complex_mul(ratio, ratio);
+        }
+        case FRACTAL_MAGNET_2: {
+            let one = vec2<f32>(1.0, 0.0);
+            let c1 = c - one;
+            let c2 = c - 2.0 * one;
+            let z2 = complex_mul(z, z);
+            let c12 = complex_mul(c1, c2);
+            let ratio = complex_div(complex_mul(z2, z) + 3.0 * complex_mul(c1, z) + c12, 3.0 * z2 + 3.0 * complex_mul(c2, z) + c12 + one);
+            return complex_mul(ratio, ratio);
+        }
+        case FRACTAL_NOVA: { return z - newton_step(z) + c; }
+        // The formula has c in it already
+        case FRACTAL_CUSTOM: { return custom_step(z, c); }
+        default: { return complex_mul(z, z) + c; }
+    }
+}
+
+fn escape_radius() -> f32 {
+    switch u.fractal_type {
+        case FRACTAL_MAGNET_1, FRACTAL_MAGNET_2: { return max(u.bailout, MAGNET_BAILOUT); }
+        case FRACTAL_NOVA: { return NOVA_BAILOUT; }
+        default: { return u.bailout; }
+    }
+}
+
+// Magnets converge to the fixed point at 1, novas to wherever their steps die down
+fn converged(z: vec2<f32>, previous: vec2<f32>) -> bool {
+    var offset: vec2<f32>;
+    switch u.fractal_type {
+        case FRACTAL_MAGNET_1, FRACTAL_MAGNET_2: { offset = z - vec2<f32>(1.0, 0.0); }
+        case FRACTAL_NOVA: { offset = z - previous; }
+        default: { return false; }
+    }
+    return dot(offset, offset) < CONVERGENCE_TOLERANCE * CONVERGENCE_TOLERANCE;
+}
+
+// The rational maps and typed-in formulas don't get derivatives tracked, so they can't do distance estimates
+fn has_distance_estimate() -> bool {
+    return u.fractal_type != FRACTAL_MAGNET_1 && u.fractal_type != FRACTAL_MAGNET_2 && u.fractal_type != FRACTAL_NOVA && u.fractal_type != FRACTAL_CUSTOM;
+}
+
+// How fast escaping orbits grow, |z| -> |z|^degree. Anything under 1 doesn't grow them steadily, so it gets banded.
+fn escape_degree() -> f32 {
+    if u.fractal_type == FRACTAL_MULTIBROT { return u.exponent; }
+    return 2.0;
+}
+
+fn pixel_size() -> f32 {
+    return 2.0 * u.scale / u.viewport_height;
+}
+
+// Distance estimates need dz/dc for the mandelbrot set or dz/dz0 for a julia set. Tracking it multiplied by the pixel
+// size keeps it from overflowing at deep zoom, and makes the estimate come out in pixels: |z| ln|z| / |dz · pixel size|
+//
+// The abs and conjugate variants aren't complex differentiable, so for those it's the derivative along the real axis,
+// pushed through each step's jacobian. Close enough to draw even lines with.
+fn derivative_start() -> vec2<f32> {
+    if u.mode == MODE_JULIA { return vec2<f32>(pixel_size(), 0.0); }
+    return vec2<f32>(0.0);
+}
+
+fn derivative_step(z: vec2<f32>, derivative: vec2<f32>) -> vec2<f32> {
+    // Folding a coordinate with abs flips the sign of its derivative wherever the coordinate was negative
+    let square_sign = sign(z.x * z.x - z.y * z.y);
+    var next: vec2<f32>;
+    switch u.fractal_type {
+        case FRACTAL_MULTIBROT: { next = complex_mul(u.exponent * complex_pow(z, u.exponent - 1.0), derivative); }
+        case FRACTAL_BURNING_SHIP: { next = 2.0 * complex_mul(abs(z), sign(z) * derivative); }
+        case FRACTAL_TRICORN: { next = conjugate(2.0 * complex_mul(z, derivative)); }
+        case FRACTAL_CELTIC: {
+            let product = 2.0 * complex_mul(z, derivative);
+            next = vec2<f32>(square_sign * product.x, product.y);
+        }
+        case FRACTAL_BUFFALO: {
+            let product = 2.0 * complex_mul(z, derivative);
+            let folded = 2.0 * complex_mul(abs(z), sign(z) * derivative);
+            next = vec2<f32>(square_sign * product.x, -folded.y);
+        }
+        case FRACTAL_PERPENDICULAR: {
+            next = conjugate(2.0 * complex_mul(vec2<f32>(abs(z.x), z.y), vec2<f32>(sign(z.x) * derivative.x, derivative.y)));
+        }
+        default: { next = 2.0 * complex_mul(z, derivative); }
+    }
+    if u.mode == MODE_JULIA { return next; }
+    return next + vec2<f32>(pixel_size(), 0.0);
+}
+
+fn distance_estimate(z: vec2<f32>, derivative: vec2<f32>) -> f32 {
+    return length(z) * log(length(z)) / length(derivative);
+}
+
+// The main cardioid and the period 2 bulb have closed forms, which saves running their points all the way out to
+// max_iter. Done in df64 either way since it's only once per pixel, and points just outside the edge would get caught
+// otherwise. Must match known_period() in fractal.rs.
+fn known_period(c: vec4<f32>) -> u32 {
+    if u.mode == MODE_JULIA || u.fractal_type != FRACTAL_MANDELBROT { return 0u; }
+    let x = df_sub(c.xy, vec2<f32>(0.25, 0.0));
+    let y_squared = df_mul(c.zw, c.zw);
+    let q = df_add(df_mul(x, x), y_squared);
+    if df_sub(df_mul(q, df_add(q, x)), 0.25 * y_squared).x <= 0.0 { return 1u; }
+    let x2 = df_add(c.xy, vec2<f32>(1.0, 0.0));
+    if df_add(df_mul(x2, x2), y_squared).x <= 0.0625 { return 2u; }
+    return 0u;
+}
+
+// Brent's cycle detection: every iterate gets compared to a saved one, which gets replaced each time the number of
+// steps since it reaches the next power of two. Once an orbit settles into a cycle this catches it within a few laps.
+struct Periodicity {
+    saved: vec4<f32>,
+    steps: u32,
+    window: u32,
+};
+
+fn periodicity_start(z: vec4<f32>) -> Periodicity {
+    return Periodicity(z, 0u, 1u);
+}
+
+// Call once per iteration, with z as a df64 so the difference holds up at df64 zoom levels. Returns the period once
+// the orbit comes back within a small fraction of a pixel of the saved iterate, 0 until then.
+fn periodicity_check(periodicity: ptr<function, Periodicity>, z: vec4<f32>) -> u32 {
+    (*periodicity).steps++;
+    let difference = dc_add(z, -(*periodicity).saved).xz;
+    let epsilon = 1e-3 * pixel_size();
+    if dot(difference, difference) < epsilon * epsilon { return (*periodicity).steps; }
+    if (*periodicity).steps == (*periodicity).window {
+        (*periodicity).saved = z;
+        (*periodicity).steps = 0u;
+        (*periodicity).window *= 2u;
+    }
+    return 0u;
+}
+
+fn iterate_f32(offset: vec2<f32>) -> Escape {
+    let position = u.center + offset;
+    var z = vec2<f32>(0.0, 0.0);
+    var c = position;
+    if u.mode == MODE_JULIA {
+        z = position;
+        c = u.julia_c;
+    } else if u.fractal_type == FRACTAL_NOVA {
+        // Parameter space orbits start at a root of the polynomial by convention. That's only a critical point of the step
+        // when the relaxation is 1.
+        z = u.newton[0].zw;
+    }
+
+    // Newton basins are in the z plane, so there's no julia version to speak of
+    if u.fractal_type == FRACTAL_NEWTON { return iterate_newton(position); }
+
+    let period = known_period(vec4<f32>(c.x, 0.0, c.y, 0.0));
+    if period != 0u && !interior_needs_orbit() { return Escape(u.max_iter, z, 0.0, period, false, orbit_start()); }
+
+    var derivative = derivative_start();
+    var previous = vec2<f32>(0.0);
+    var previous_derivative = vec2<f32>(0.0);
+    var periodicity = periodicity_start(vec4<f32>(z.x, 0.0, z.y, 0.0));
+    var orbit = orbit_start();
+    var n = 0u;
+    for (; n < u.max_iter; n++) {
+        if dot(z, z) > escape_radius() * escape_radius() { break; }
+        var next = fractal_step(z, c);
+        var next_derivative = derivative_step(z, derivative);
+        if u.fractal_type == FRACTAL_PHOENIX {
+            next += c.y * previous;
+            next_derivative += c.y * previous_derivative;
+        }
+        previous = z;
+        previous_derivative = derivative;
+        z = next;
+        derivative = next_derivative;
+        orbit = orbit_update(orbit, z);
+        if converged(z, previous) { return Escape(n + 1u, z, 0.0, 0u, true, orbit); }
+        let period = periodicity_check(&periodicity, vec4<f32>(z.x, 0.0, z.y, 0.0));
+        if period != 0u && !interior_needs_last_z() { return Escape(u.max_iter, z, interior_distance(z, c, period), period, false, orbit); }
+    }
+    return Escape(n, z, distance_estimate(z, derivative), 0u, false, orbit);
+}
+
+fn orbit_start() -> Orbit {
+    return Orbit(TRAP_FAR, vec2<f32>(-1.0), 0u, TRAP_FAR, 0u);
+}
+
+// Call with every iterate after the starting one. Must match FractalParams::sample() in fractal.rs.
+fn orbit_update(orbit: Orbit, z: vec2<f32>) -> Orbit {
+    var next = orbit;
+    next.steps++;
+    if dot(z, z) < next.closest {
+        next.closest = dot(z, z);
+        next.atom = next.steps;
+    }
+    if u.trap_shape != TRAP_IMAGE {
+        next.trap = min(next.trap, trap_distance(z));
+    } else if next.trap_image.x < 0.0 {
+        // Only the first point in the image counts, the rest of the orbit just gets drawn over
+        let image = trap_image_position(z);
+        if all(image >= vec2<f32>(0.0)) && all(image <= vec2<f32>(1.0)) { next.trap_image = image; }
+    }
+    return next;
+}
+
+// Interior coloring that looks at the orbit can't take the closed form shortcut for the cardioid and period 2 bulb
+fn interior_needs_orbit() -> bool {
+    return u.interior_mode != INTERIOR_BLACK && u.interior_mode != INTERIOR_PERIOD;
+}
+
+// And final |z| and arg z are of the very last iterate, so those can't stop as soon as they find a cycle either
+fn interior_needs_last_z() -> bool {
+    return u.interior_mode == INTERIOR_MAGNITUDE || u.interior_mode == INTERIOR_ANGLE;
+}
+
+// Interior distance estimate in pixels, from the derivatives of the period p map at the attracting cycle. z only has to
+// be close to the cycle, Newton's method takes it the rest of the way. Only the mandelbrot set's parameter space has
+// one. Must match FractalParams::interior_distance() in fractal.rs.
+fn interior_distance(start: vec2<f32>, c: vec2<f32>, period: u32) -> f32 {
+    if u.interior_mode != INTERIOR_DISTANCE || u.mode == MODE_JULIA || u.fractal_type != FRACTAL_MANDELBROT { return 0.0; }
+    let one = vec2<f32>(1.0, 0.0);
+    var cycle = start;
+    for (var i = 0u; i < INTERIOR_NEWTON_STEPS; i++) {
+        var z = cycle;
+        var dz = one;
+        for (var j = 0u; j < period; j++) {
+            dz = 2.0 * complex_mul(z, dz);
+            z = complex_mul(z, z) + c;
+        }
+        cycle -= complex_div(z - cycle, dz - one);
+    }
+
+    var z = cycle;
+    var dz = one;
+    var dc = vec2<f32>(0.0);
+    var dzdz = vec2<f32>(0.0);
+    var dcdz = vec2<f32>(0.0);
+    for (var j = 0u; j < period; j++) {
+        dcdz = 2.0 * (complex_mul(z, dcdz) + complex_mul(dc, dz));
+        dzdz = 2.0 * (complex_mul(z, dzdz) + complex_mul(dz, dz));
+        dc = 2.0 * complex_mul(z, dc) + one;
+        dz = 2.0 * complex_mul(z, dz);
+        z = complex_mul(z, z) + c;
+    }
+    let distance = (1.0 - dot(dz, dz)) / length(dcdz + complex_div(complex_mul(dzdz, dc), one - dz));
+    return max(distance, 0.0) / pixel_size();
+}
+
+// In the trap's own frame, where its lines run along the axes
+fn trap_local(z: vec2<f32>) -> vec2<f32> {
+    let offset = z - u.trap_center;
+    return vec2<f32>(offset.x * cos(u.trap_angle) + offset.y * sin(u.trap_angle), offset.y * cos(u.trap_angle) - offset.x * sin(u.trap_angle));
+}
+
+// Texture coordinates in the trap image, which is trap_radius tall each way from the center and as wide as its aspect
+// says. Anything outside 0 to 1 misses it. Must match OrbitTrap::image_position() in fractal.rs.
+fn trap_image_position(z: vec2<f32>) -> vec2<f32> {
+    let local = trap_local(z) / u.trap_radius;
+    return vec2<f32>(local.x / u.trap_aspect, -local.y) * 0.5 + 0.5;
+}
+
+// How far z is from the orbit trap. Must match OrbitTrap::distance() in fractal.rs.
+fn trap_distance(z: vec2<f32>) -> f32 {
+    let offset = z - u.trap_center;
+    let local = trap_local(z);
+    switch u.trap_shape {
+        case TRAP_POINT: { return length(offset); }
+        case TRAP_LINE: { return abs(local.y); }
+        case TRAP_CIRCLE: { return abs(length(offset) - u.trap_radius); }
+        case TRAP_CROSS: { return min(abs(local.x), abs(local.y)); }
+        default: { return TRAP_FAR; }
+    }
+}
+
+// Must match OrbitTrap::brightness() in fractal.rs
+fn trap_brightness(distance: f32) -> f32 {
+    if u.trap_shape == TRAP_NONE || u.trap_shape == TRAP_IMAGE { return 1.0; }
+    return exp(-distance / u.trap_width);
+}
+
+// Only ever runs plain mandelbrot iterations, since the other fractal types stick to f32.
+// The offset from the center is small enough that plain f32 holds it fine, it's only the sum that needs the extra bits
+fn iterate_df64(offset: vec2<f32>) -> Escape {
+    let position = vec4<f32>(df_add(vec2<f32>(u.center.x, u.center_lo.x), vec2<f32>(offset.x, 0.0)), df_add(vec2<f32>(u.center.y, u.center_lo.y), vec2<f32>(offset.y, 0.0)));
+    var z = vec4<f32>(0.0);
+    var c = position;
+    if u.mode == MODE_JULIA {
+        z = position;
+        c = vec4<f32>(u.julia_c.x, u.julia_c_lo.x, u.julia_c.y, u.julia_c_lo.y);
+    }
+
+    let period = known_period(c);
+    if period != 0u && !interior_needs_orbit() { return Escape(u.max_iter, z.xz, 0.0, period, false, orbit_start()); }
+
+    // The derivative doesn't need anywhere near the precision z does
+    var derivative = derivative_start();
+    var periodicity = periodicity_start(z);
+    var orbit = orbit_start();
+    var n = 0u;
+    for (; n < u.max_iter; n++) {
+        if dc_length_squared(z) > u.bailout * u.bailout { break; }
+        derivative = derivative_step(z.xz, derivative);
+        z = dc_add(dc_square(z), c);
+        orbit = orbit_update(orbit, z.xz);
+        let period = periodicity_check(&periodicity, z);
+        if period != 0u && !interior_needs_last_z() { return Escape(u.max_iter, z.xz, interior_distance(z.xz, c.xz, period), period, false, orbit); }
+    }
+    return Escape(n, z.xz, distance_estimate(z.xz, derivative), 0u, false, orbit);
+}
+
+// The average of ln|r(1 - 2x)|, the logistic map's log derivative, along the orbit of x = 0.5, with r switching between
+// a and b as the sequence says. Orbits that leave [0, 1] head off to infinity and don't get an exponent at all. Must
+// match FractalParams::lyapunov_exponent() in fractal.rs.
+struct Lyapunov {
+    exponent: f32,
+    diverged: bool,
+}
+
+fn lyapunov_exponent(ab: vec2<f32>) -> Lyapunov {
+    var x = 0.5;
+    var total = 0.0;
+    for (var n = 0u; n < LYAPUNOV_WARMUP + u.max_iter; n++) {
+        let r = select(ab.x, ab.y, (u.sequence >> (n % u.sequence_length) & 1u) == 1u);
+        if n >= LYAPUNOV_WARMUP { total += log(max(abs(r * (1.0 - 2.0 * x)), LYAPUNOV_FLOOR)); }
+        x = r * x * (1.0 - x);
+        if x < 0.0 || x > 1.0 { return Lyapunov(0.0, true); }
+    }
+    return Lyapunov(total / f32(u.max_iter), false);
+}
+
+// Stable orbits in gold and chaotic ones in blue, both fading to black where the exponent crosses 0. Must match
+// lyapunov_color() in fractal.rs.
+fn lyapunov_color(exponent: f32) -> vec3<f32> {
+    if exponent < 0.0 { return vec3<f32>(1.0, 0.8, 0.1) * (1.0 - exp(exponent)); }
+    return vec3<f32>(0.1, 0.3, 1.0) * (1.0 - exp(-exponent));
+}
+
+fn pixel_offset(in: VertexOutput) -> vec2<f32> {
+    return in.uv * vec2<f32>(u.scale * u.aspect, u.scale);
+}
+
+// What the palette gets indexed with. Must match Sample::value in fractal.rs.
+fn color_value(escape: Escape) -> f32 {
+    // Converged orbits get the other half of the palette, so they stand out from escaped ones next to them
+    if escape.converged { return f32(escape.n) + 25.0; }
+    switch u.color_mode {
+        // The fractional part makes up for how far past the bailout the orbit overshot, so bands blend into each other.
+        // Orbits that don't grow any faster than linearly have no overshoot to make up for.
+        case COLOR_SMOOTH: {
+            if escape_degree() <= 1.0 { return f32(escape.n); }
+            return f32(escape.n) + 1.0 - log(log(length(escape.z))) / log(escape_degree());
+        }
+        default: { return f32(escape.n); }
+    }
+}
+
+// Must match interior_color() in fractal.rs
+fn interior_color(escape: Escape) -> vec3<f32> {
+    switch u.interior_mode {
+        case INTERIOR_MAGNITUDE: { return palette(25.0 * length(escape.z)); }
+        case INTERIOR_ANGLE: { return palette(50.0 * (complex_arg(escape.z) / 6.2831853 + 0.5)); }
+        // The rest need a cycle or a record to go by, and stay black without one
+        case INTERIOR_PERIOD: { if escape.period != 0u { return palette(5.0 * f32(escape.period)); } }
+        case INTERIOR_DISTANCE: { if escape.period != 0u { return vec3<f32>(clamp(escape.distance / u.line_thickness, 0.0, 1.0)); } }
+        case INTERIOR_ATOM_DOMAIN: { if escape.orbit.atom != 0u { return palette(5.0 * f32(escape.orbit.atom)); } }
+        default: {}
+    }
+    return vec3<f32>(0.0);
+}
+
+// Must match color() in fractal.rs
+fn shade(escape: Escape) -> vec4<f32> {
+    if escape.n == u.max_iter { return vec4<f32>(srgb_to_linear(interior_color(escape)), 1.0); }
+    if u.fractal_type == FRACTAL_NEWTON {
+        // Colored by the root it got to, darker the longer that took
+        let brightness = pow(0.93, f32(escape.n));
+        return vec4<f32>(srgb_to_linear(palette(newton_value(escape.z)) * brightness), 1.0);
+    }
+    if u.color_mode == COLOR_DISTANCE && has_distance_estimate() {
+        // Black right at the boundary, fading to white a line thickness out
+        let brightness = clamp(escape.distance / u.line_thickness, 0.0, 1.0);
+        return vec4<f32>(srgb_to_linear(vec3<f32>(brightness)), 1.0);
+    }
+    // The trap only changes how bright it is, so the bands or smooth gradient still show through
+    let color = srgb_to_linear(palette(color_value(escape)) * trap_brightness(escape.orbit.trap));
+    if u.trap_shape == TRAP_IMAGE && escape.orbit.trap_image.x >= 0.0 {
+        // Where the image is see through, so is the trap
+        let texel = textureSampleLevel(t, s, escape.orbit.trap_image, 0.0);
+        return vec4<f32>(mix(color, texel.rgb, texel.a), 1.0);
+    }
+    return vec4<f32>(color, 1.0);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t, s, (in.normal.xy * vec2<f32>(1, 1) + 1.0) * 0.5);
+    let offset = pixel_offset(in);
+    // A different plane with a different map, so none of the escape time machinery applies
+    if u.fractal_type == FRACTAL_LYAPUNOV {
+        let lyapunov = lyapunov_exponent(u.center + offset);
+        if lyapunov.diverged { return vec4<f32>(0.0, 0.0, 0.0, 1.0); }
+        return vec4<f32>(srgb_to_linear(lyapunov_color(lyapunov.exponent)), 1.0);
+    }
+    var escape: Escape;
+    if u.precision_mode == PRECISION_DF64 {
+        escape = iterate_df64(offset);
+    } else {
+        escape = iterate_f32(offset);
+    }
+    return shade(escape);
 }
 
+// Density images come in as a texture the size of the pane, already colored
+@fragment
+fn fs_density(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = vec2<f32>(in.uv.x, -in.uv.y) * 0.5 + 0.5;
+    return vec4<f32>(srgb_to_linear(textureSample(t, s, uv).rgb), 1.0);
+}